@@ -17,17 +17,27 @@
  */
 
 use crate::com_ubuntu_hwctl::{
-    Call_GetCertificationStatus, CertificationSource, CertificationStatus, Kernel, State,
-    VarlinkInterface, OS,
+    Call_CancelCheck, Call_ExportRequest, Call_GetCertificationStatus, Call_GetHistory,
+    Call_ImportResponse, CertificationSource, CertificationStatus, ChangeKind, Compatibility,
+    HardwareChange, HistoryEntry, Kernel, Provenance, State, StateSource, VarlinkInterface, OS,
 };
 
+use std::cell::Cell;
+use std::io::{BufRead, BufReader};
 use std::process::ExitCode;
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use varlink::ConnectionHandler;
 
 #[cfg(not(test))]
 use hwlib::models::request_validators::Paths;
 
+use hwlib::cache::CannotSaveCache;
 use hwlib::models::request_validators::CertificationStatusRequest;
-use hwlib::{check_certification_status, helpers, CheckCertificationSource};
+use hwlib::{
+    bundle, check_certification_status_failover, helpers, CancellationToken, CheckCancelled,
+    CheckCertificationSource, CustomUrlNotAllowed, HttpTransport, Integration,
+    PublicCertificationStatus, StaleStatus,
+};
 
 mod com_ubuntu_hwctl;
 
@@ -72,6 +82,187 @@ fn create_test_hardware_data(arch: String) -> CertificationStatusRequest {
     }
 }
 
+fn to_varlink_os(release: &hwlib::models::software::OS) -> OS {
+    OS {
+        codename: release.codename.clone(),
+        distributor: release.distributor.clone(),
+        version: release.version.clone(),
+        kernel: Kernel {
+            name: release.kernel.name.clone(),
+            version: release.kernel.version.clone(),
+            signature: release.kernel.signature.clone(),
+            loaded_modules: release.kernel.loaded_modules.clone(),
+        },
+    }
+}
+
+fn to_varlink_status(status: &hwlib::cache::CertificationStatus) -> CertificationStatus {
+    match status {
+        hwlib::cache::CertificationStatus::Certified => CertificationStatus::Certified,
+        hwlib::cache::CertificationStatus::NotSeen => CertificationStatus::NotSeen,
+        hwlib::cache::CertificationStatus::Unknown => CertificationStatus::Unknown,
+        hwlib::cache::CertificationStatus::CertifiedImageExists => {
+            CertificationStatus::CertifiedImageExists
+        }
+        hwlib::cache::CertificationStatus::RelatedCertifiedSystemExists => {
+            CertificationStatus::RelatedCertifiedSystemExists
+        }
+    }
+}
+
+fn to_varlink_state(response: &PublicCertificationStatus) -> State {
+    let (status, url, os) = response.get_status();
+    let (stale, stale_reason) = response.stale_status();
+    let (valid_cache, source, remote_access_enabled, cached_server_url, hardware_mismatch) =
+        response.extra_data();
+    let compatibility = response.compatibility();
+    State {
+        status: to_varlink_status(&status),
+        certified_url: url,
+        available_releases: Some(os.iter().map(to_varlink_os).collect()),
+        valid_cache,
+        hardware_mismatch,
+        software_changed: response.software_changed(),
+        stale,
+        stale_reason,
+        source: match source {
+            hwlib::CertificationSource::Cache => StateSource::cache,
+            hwlib::CertificationSource::Server => StateSource::server,
+            hwlib::CertificationSource::Imported => StateSource::imported,
+        },
+        remote_access_enabled,
+        server_url: cached_server_url,
+        redaction_policy: response.redaction_policy().name().to_string(),
+        hardware_fingerprint: response.hardware_fingerprint(),
+        hardware_changes: response
+            .hardware_changes()
+            .into_iter()
+            .map(|change| HardwareChange {
+                field: change.field,
+                kind: match change.kind {
+                    hwlib::ChangeKind::Added => ChangeKind::added,
+                    hwlib::ChangeKind::Removed => ChangeKind::removed,
+                    hwlib::ChangeKind::Modified => ChangeKind::modified,
+                },
+                old: change.old,
+                new: change.new,
+            })
+            .collect(),
+        compatibility: Compatibility {
+            release_certified: compatibility.release_certified,
+            kernel_certified: compatibility.kernel_certified,
+            certified_flavours: compatibility.certified_flavours,
+            newer: compatibility.newer.iter().map(to_varlink_os).collect(),
+            older: compatibility.older.iter().map(to_varlink_os).collect(),
+            recommended: compatibility.recommended.as_ref().map(to_varlink_os),
+        },
+        next_retry_at: response.next_retry_at(),
+        refresh_pending: response.refresh_pending(),
+        provenance: response.provenance().map(|provenance| Provenance {
+            server: provenance.server,
+            answered_at: provenance.answered_at,
+            imported_at: provenance.imported_at,
+            bundle_checksum: provenance.bundle_checksum,
+        }),
+    }
+}
+
+#[cfg(test)]
+fn collect_hardware_data() -> Result<CertificationStatusRequest, anyhow::Error> {
+    Ok(create_test_hardware_data("x86_64".to_string()))
+}
+
+#[cfg(not(test))]
+fn collect_hardware_data() -> Result<CertificationStatusRequest, anyhow::Error> {
+    CertificationStatusRequest::new(Paths::default())
+}
+
+/// The checks in progress, which CancelCheck cancels.
+static PENDING_CHECKS: Mutex<Vec<CancellationToken>> = Mutex::new(Vec::new());
+
+/// Runs a check that can be cancelled with CancelCheck.
+fn run_check(
+    server_urls: &[String],
+    source: CheckCertificationSource,
+    hardware: &CertificationStatusRequest,
+) -> anyhow::Result<PublicCertificationStatus> {
+    let cancel = CancellationToken::new();
+    PENDING_CHECKS.lock().unwrap().push(cancel.clone());
+    let transport = Arc::new(HttpTransport::new(Integration::Daemon));
+    let response = check_certification_status_failover(
+        server_urls,
+        source,
+        hardware,
+        None,
+        Some(transport),
+        &cancel,
+    );
+    PENDING_CHECKS
+        .lock()
+        .unwrap()
+        .retain(|pending| !pending.same_as(&cancel));
+    response
+}
+
+/// Replies with the varlink error matching the failure of the request to
+/// the server that left the answer stale.
+fn reply_server_failure(
+    call: &mut dyn Call_GetCertificationStatus,
+    kind: StaleStatus,
+    reason: String,
+) -> varlink::Result<()> {
+    match kind {
+        StaleStatus::DnsError => call.reply_dns_error(reason),
+        StaleStatus::TlsError => call.reply_tls_error(reason),
+        StaleStatus::Timeout => call.reply_server_timeout(reason),
+        StaleStatus::RequestRejected { status } => {
+            call.reply_request_rejected(status as i64, reason)
+        }
+        StaleStatus::ServerFailure { status } => call.reply_server_failed(status as i64, reason),
+        StaleStatus::RateLimited { retry_after } => {
+            call.reply_rate_limited(retry_after.map(|seconds| seconds as i64), reason)
+        }
+        StaleStatus::ServerError => call.reply_invalid_response(reason),
+        _ => call.reply_connection_failed(reason),
+    }
+}
+
+thread_local! {
+    /// The user of the client whose calls the current thread handles, if known.
+    static PEER_UID: Cell<Option<u32>> = const { Cell::new(None) };
+}
+
+/// Returns the user of the process at the other end of a unix socket.
+fn peer_uid(stream: &dyn varlink::Stream) -> Option<u32> {
+    let mut credentials = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut length = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    // SAFETY: credentials and length describe a buffer large enough for the
+    // ucred structure SO_PEERCRED writes.
+    let result = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut credentials as *mut libc::ucred as *mut libc::c_void,
+            &mut length,
+        )
+    };
+    (result == 0).then_some(credentials.uid)
+}
+
+/// Whether the client of the current call is root or the user running the
+/// daemon. The socket is open to every user, so calls that change what the
+/// daemon reports for everybody are limited to them.
+fn is_privileged_peer() -> bool {
+    // SAFETY: geteuid() has no preconditions and can't fail.
+    let euid = unsafe { libc::geteuid() };
+    PEER_UID.get().is_some_and(|uid| uid == 0 || uid == euid)
+}
+
 struct ComUbuntuHwctl;
 
 impl VarlinkInterface for ComUbuntuHwctl {
@@ -80,80 +271,59 @@ impl VarlinkInterface for ComUbuntuHwctl {
         call: &mut dyn Call_GetCertificationStatus,
         r#source: CertificationSource,
         r#server_url: Option<String>,
+        r#server_urls: Option<Vec<String>>,
     ) -> varlink::Result<()> {
         let source = match r#source {
             CertificationSource::server => CheckCertificationSource::Server,
             CertificationSource::cache => CheckCertificationSource::Cache,
             CertificationSource::auto => CheckCertificationSource::Auto,
+            CertificationSource::background => CheckCertificationSource::Background,
         };
-        #[cfg(test)]
-        let current_hardware: Result<CertificationStatusRequest, anyhow::Error> =
-            Result::Ok(create_test_hardware_data("x86_64".to_string()));
-
-        #[cfg(not(test))]
-        let current_hardware = CertificationStatusRequest::new(Paths::default());
-
+        let current_hardware = collect_hardware_data();
         if current_hardware.is_err() {
             return call.reply_cannot_collect_system_data();
         }
         let current_hardware = current_hardware.unwrap();
 
-        let response = check_certification_status(
-            r#server_url.unwrap_or_else(|| hwlib::constants::DEFAULT_SERVER_URL.to_string()),
-            source,
-            &current_hardware,
-            None,
-        );
+        let server_urls = r#server_urls
+            .filter(|server_urls| !server_urls.is_empty())
+            .or(r#server_url.map(|server_url| vec![server_url]))
+            .unwrap_or_else(|| hwlib::cache::HWCache::new(None).get_server_urls());
+        let ask_server = source == CheckCertificationSource::Server;
+        let response = run_check(&server_urls, source, &current_hardware);
 
-        if response.is_err() {
-            return call.reply_access_denied();
+        if let Err(error) = &response {
+            if error.downcast_ref::<CheckCancelled>().is_some() {
+                return call.reply_check_cancelled();
+            }
+            if error.downcast_ref::<CustomUrlNotAllowed>().is_some() {
+                return call.reply_access_denied();
+            }
+            if let Some(error) = error.downcast_ref::<CannotSaveCache>() {
+                return call.reply_cannot_save_cache(format!("{:#}", error.0));
+            }
+            return call.reply_check_failed(format!("{:#}", error));
         }
         let response = response.unwrap();
 
-        let (status, url, os) = response.get_status();
-        let (stale, stale_reason) = response.stale_status();
-        let (valid_cache, source, remote_access_enabled, server_url, hardware_mismatch) =
-            response.extra_data();
-        let response_varlink = State {
-            status: match status {
-                hwlib::cache::CertificationStatus::Certified => CertificationStatus::Certified,
-                hwlib::cache::CertificationStatus::NotSeen => CertificationStatus::NotSeen,
-                hwlib::cache::CertificationStatus::Unknown => CertificationStatus::Unknown,
-                hwlib::cache::CertificationStatus::CertifiedImageExists => {
-                    CertificationStatus::CertifiedImageExists
-                }
-                hwlib::cache::CertificationStatus::RelatedCertifiedSystemExists => {
-                    CertificationStatus::RelatedCertifiedSystemExists
-                }
-            },
-            certified_url: url,
-            available_releases: Some(
-                os.iter()
-                    .map(|release| OS {
-                        codename: release.codename.clone(),
-                        distributor: release.distributor.clone(),
-                        version: release.version.clone(),
-                        kernel: Kernel {
-                            name: release.kernel.name.clone(),
-                            version: release.kernel.version.clone(),
-                            signature: release.kernel.signature.clone(),
-                            loaded_modules: release.kernel.loaded_modules.clone(),
-                        },
-                    })
-                    .collect(),
-            ),
-            valid_cache: valid_cache,
-            hardware_mismatch: hardware_mismatch,
-            stale: stale,
-            stale_reason: stale_reason,
-            source: match source {
-                hwlib::CertificationSource::Cache => CertificationSource::cache,
-                hwlib::CertificationSource::Server => CertificationSource::server,
-            },
-            remote_access_enabled: remote_access_enabled,
-            server_url: server_url,
-        };
-        return call.reply(response_varlink);
+        // Clients asking the server want its answer rather than the cached one.
+        if ask_server && response.stale_kind().is_failure() {
+            let (_, reason) = response.stale_status();
+            return reply_server_failure(call, response.stale_kind(), reason.unwrap_or_default());
+        }
+
+        call.reply(to_varlink_state(&response))?;
+        if response.refresh_pending() {
+            // The reply has already been sent, so the caller doesn't wait for
+            // the server. The refresh runs on the thread of the connection,
+            // which counts against `MAX_CONNECTIONS` until it is done.
+            let _ = run_check(
+                &server_urls,
+                CheckCertificationSource::Auto,
+                &current_hardware,
+            );
+        }
+        Ok(())
     }
 
     fn set_remote_access(
@@ -162,9 +332,135 @@ impl VarlinkInterface for ComUbuntuHwctl {
         r#enabled: bool,
     ) -> varlink::Result<()> {
         let mut cache = hwlib::cache::HWCache::new(None);
-        cache.set_remote_access_enabled(r#enabled);
+        if let Err(error) = cache.set_remote_access_enabled(r#enabled) {
+            return call.reply_cannot_save_cache(format!("{:#}", error));
+        }
         return call.reply();
     }
+
+    fn get_history(&self, call: &mut dyn Call_GetHistory) -> varlink::Result<()> {
+        let entries = hwlib::cache::HWCache::new(None)
+            .get_history()
+            .into_iter()
+            .map(|entry| HistoryEntry {
+                timestamp: entry.timestamp,
+                server: entry.server,
+                hardware_fingerprint: entry.hardware_fingerprint,
+                status: to_varlink_status(&entry.status),
+                stale: entry.stale != StaleStatus::Valid,
+                stale_reason: entry.stale_reason,
+                latency_ms: entry.latency_ms as i64,
+            })
+            .collect();
+        call.reply(entries)
+    }
+
+    fn cancel_check(&self, call: &mut dyn Call_CancelCheck) -> varlink::Result<()> {
+        let pending = PENDING_CHECKS.lock().unwrap();
+        pending.iter().for_each(CancellationToken::cancel);
+        call.reply(!pending.is_empty())
+    }
+
+    fn export_request(&self, call: &mut dyn Call_ExportRequest) -> varlink::Result<()> {
+        let Ok(current_hardware) = collect_hardware_data() else {
+            return call.reply_cannot_collect_system_data();
+        };
+        match bundle::export_request(&current_hardware, None).and_then(|bundle| bundle.to_json()) {
+            Ok(json) => call.reply(json),
+            Err(error) => call.reply_invalid_bundle(format!("{:#}", error)),
+        }
+    }
+
+    fn import_response(
+        &self,
+        call: &mut dyn Call_ImportResponse,
+        r#bundle: String,
+    ) -> varlink::Result<()> {
+        if !is_privileged_peer() {
+            return call.reply_access_denied();
+        }
+        let Ok(current_hardware) = collect_hardware_data() else {
+            return call.reply_cannot_collect_system_data();
+        };
+        let response = bundle::ResponseBundle::from_json(&r#bundle)
+            .and_then(|bundle| bundle::import_response(&bundle, &current_hardware, None));
+        match response {
+            Ok(response) => call.reply(to_varlink_state(&response)),
+            Err(error) if error.downcast_ref::<CustomUrlNotAllowed>().is_some() => {
+                call.reply_access_denied()
+            }
+            Err(error) => match error.downcast_ref::<CannotSaveCache>() {
+                Some(error) => call.reply_cannot_save_cache(format!("{:#}", error.0)),
+                None => call.reply_invalid_bundle(format!("{:#}", error)),
+            },
+        }
+    }
+}
+
+/// The connections being served, at most `MAX_CONNECTIONS`.
+#[derive(Default)]
+struct Connections {
+    count: Mutex<usize>,
+    released: Condvar,
+}
+
+/// A connection being served, until dropped.
+struct ConnectionSlot(Arc<Connections>);
+
+impl Connections {
+    /// Waits until another connection can be served.
+    fn acquire(self: &Arc<Self>) -> ConnectionSlot {
+        let mut count = self.count.lock().unwrap_or_else(PoisonError::into_inner);
+        while *count >= hwlib::constants::MAX_CONNECTIONS {
+            count = self
+                .released
+                .wait(count)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        *count += 1;
+        ConnectionSlot(self.clone())
+    }
+
+    fn is_idle(&self) -> bool {
+        *self.count.lock().unwrap_or_else(PoisonError::into_inner) == 0
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        let mut count = self.0.count.lock().unwrap_or_else(PoisonError::into_inner);
+        *count -= 1;
+        self.0.released.notify_one();
+    }
+}
+
+/// Handles the calls of a client until it disconnects.
+fn serve_connection(service: &varlink::VarlinkService, mut stream: Box<dyn varlink::Stream>) {
+    PEER_UID.set(peer_uid(stream.as_ref()));
+    let Ok((reader, mut writer)) = stream.split() else {
+        return;
+    };
+    let mut reader = BufReader::new(reader);
+    let mut iface: Option<String> = None;
+    loop {
+        match service.handle(&mut reader, &mut writer, iface.clone()) {
+            Ok((_, next_iface)) => {
+                iface = next_iface;
+                match reader.fill_buf() {
+                    Err(_) | Ok([]) => break,
+                    _ => {}
+                }
+            }
+            Err(error) => {
+                match error.kind() {
+                    varlink::ErrorKind::ConnectionClosed | varlink::ErrorKind::SerdeJsonDe(_) => {}
+                    _ => eprintln!("Worker error: {:?}", error),
+                }
+                let _ = stream.shutdown();
+                break;
+            }
+        }
+    }
 }
 
 fn create_server(socket_file: String, timeout: u64) -> Result<(), varlink::Error> {
@@ -173,22 +469,41 @@ fn create_server(socket_file: String, timeout: u64) -> Result<(), varlink::Error
     let socket_file = format!("unix://{};mode=0666", socket_file);
     println!("Starting varlink service on socket: {}", socket_file);
 
-    let service = varlink::VarlinkService::new(
+    let service = Arc::new(varlink::VarlinkService::new(
         "org.varlink",
         "hwctl service",
         "0.1",
         "http://varlink.org",
         vec![Box::new(varlink_interface)],
-    );
-
-    return varlink::listen(
-        service,
-        &socket_file,
-        &varlink::ListenConfig {
-            idle_timeout: timeout,
-            ..Default::default()
-        },
-    );
+    ));
+
+    // varlink::listen() doesn't tell the handlers who the client is, so the
+    // connections are accepted here to get the credentials of the peer.
+    let listener = varlink::Listener::new(&socket_file)?;
+    listener.set_nonblocking(false)?;
+    let connections = Arc::new(Connections::default());
+    loop {
+        // Clients beyond the limit wait in the socket backlog.
+        let slot = connections.acquire();
+        let stream = match listener.accept(timeout * 1000) {
+            Ok(stream) => stream,
+            Err(error) if matches!(error.kind(), varlink::ErrorKind::Timeout) => {
+                drop(slot);
+                // The daemon exits once idle, it is started again on demand.
+                if connections.is_idle() {
+                    return Err(error);
+                }
+                continue;
+            }
+            Err(error) => return Err(error),
+        };
+        let service = service.clone();
+        std::thread::spawn(move || {
+            // Released even if the handler panics.
+            let _slot = slot;
+            serve_connection(&service, stream);
+        });
+    }
 }
 
 fn main() -> ExitCode {
@@ -219,6 +534,7 @@ fn main() -> ExitCode {
 
 #[cfg(test)]
 mod tests {
+    use std::os::unix::fs::PermissionsExt;
     use std::thread::{sleep, spawn};
 
     use super::*;
@@ -228,6 +544,33 @@ mod tests {
         // Keep the temp dir alive until the end of the test
     }
 
+    #[test]
+    fn test_connections_are_limited() {
+        let connections = Arc::new(Connections::default());
+        let slots: Vec<_> = (0..hwlib::constants::MAX_CONNECTIONS)
+            .map(|_| connections.acquire())
+            .collect();
+        let waiting = {
+            let connections = connections.clone();
+            spawn(move || drop(connections.acquire()))
+        };
+        sleep(std::time::Duration::from_millis(100));
+        assert!(!waiting.is_finished());
+
+        // A connection whose handler panics is released too.
+        let mut slots = slots.into_iter();
+        let slot = slots.next().unwrap();
+        assert!(spawn(move || {
+            let _slot = slot;
+            panic!("handler failed");
+        })
+        .join()
+        .is_err());
+        waiting.join().unwrap();
+        drop(slots);
+        assert!(connections.is_idle());
+    }
+
     #[test]
     fn test_varlink_interface() {
         let temp_dir = test_temp_dir!();
@@ -257,7 +600,7 @@ mod tests {
         let mut hwctl_service = com_ubuntu_hwctl::VarlinkClient::new(client_connection);
 
         let reply = hwctl_service
-            .get_certification_status(com_ubuntu_hwctl::CertificationSource::cache, None)
+            .get_certification_status(com_ubuntu_hwctl::CertificationSource::cache, None, None)
             .call();
 
         assert!(!reply.is_err());
@@ -268,7 +611,7 @@ mod tests {
 
         hwctl_service.set_remote_access(true).call().unwrap();
         let reply = hwctl_service
-            .get_certification_status(com_ubuntu_hwctl::CertificationSource::cache, None)
+            .get_certification_status(com_ubuntu_hwctl::CertificationSource::cache, None, None)
             .call();
 
         assert!(!reply.is_err());
@@ -279,7 +622,7 @@ mod tests {
 
         hwctl_service.set_remote_access(false).call().unwrap();
         let reply = hwctl_service
-            .get_certification_status(com_ubuntu_hwctl::CertificationSource::cache, None)
+            .get_certification_status(com_ubuntu_hwctl::CertificationSource::cache, None, None)
             .call();
 
         assert!(!reply.is_err());
@@ -288,6 +631,99 @@ mod tests {
         assert!(state.status == CertificationStatus::Unknown);
         assert!(!state.remote_access_enabled);
 
+        let reply = hwctl_service.get_history().call().unwrap();
+        assert!(reply.entries.is_empty());
+
+        assert!(!hwctl_service.cancel_check().call().unwrap().cancelled);
+
+        // A server that can't be reached is reported as an error when it is
+        // asked explicitly, and as a stale answer otherwise.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let unreachable = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+        let mut cache = hwlib::cache::HWCache::new(None);
+        cache.set_remote_access_enabled(true).unwrap();
+        let error = hwctl_service
+            .get_certification_status(
+                com_ubuntu_hwctl::CertificationSource::server,
+                Some(unreachable.clone()),
+                None,
+            )
+            .call()
+            .unwrap_err();
+        assert!(
+            matches!(error.kind(), com_ubuntu_hwctl::ErrorKind::AccessDenied(_)),
+            "{:?}",
+            error
+        );
+        cache.set_allow_custom_url_enabled(true).unwrap();
+        let error = hwctl_service
+            .get_certification_status(
+                com_ubuntu_hwctl::CertificationSource::server,
+                Some(unreachable.clone()),
+                None,
+            )
+            .call()
+            .unwrap_err();
+        assert!(
+            matches!(
+                error.kind(),
+                com_ubuntu_hwctl::ErrorKind::ConnectionFailed(_)
+            ),
+            "{:?}",
+            error
+        );
+        let state = hwctl_service
+            .get_certification_status(
+                com_ubuntu_hwctl::CertificationSource::cache,
+                None,
+                Some(vec![unreachable]),
+            )
+            .call()
+            .unwrap()
+            .state;
+        assert!(state.stale);
+        cache.set_remote_access_enabled(false).unwrap();
+
+        let request = hwctl_service.export_request().call().unwrap().bundle;
+        let request = bundle::RequestBundle::from_json(&request).unwrap();
+        let mut response = bundle::ResponseBundle::new(
+            &request,
+            hwlib::constants::DEFAULT_SERVER_URL.to_string(),
+            hwlib::models::response_validators::CertificationStatusResponse::NotSeen,
+            None,
+        )
+        .unwrap();
+        let key = openssl::pkey::PKey::generate_ed25519().unwrap();
+        let key_file = temp_dir.as_path_untracked().join("signing.pem");
+        let public_key_file = temp_dir.as_path_untracked().join("signing.pub");
+        std::fs::write(&key_file, key.private_key_to_pem_pkcs8().unwrap()).unwrap();
+        std::fs::set_permissions(&key_file, std::fs::Permissions::from_mode(0o600)).unwrap();
+        std::fs::write(&public_key_file, key.public_key_to_pem().unwrap()).unwrap();
+        std::fs::set_permissions(&public_key_file, std::fs::Permissions::from_mode(0o644)).unwrap();
+        response.sign(&key_file).unwrap();
+        // Bundles aren't imported until their signing key is trusted.
+        assert!(hwctl_service
+            .import_response(response.to_json().unwrap())
+            .call()
+            .is_err());
+        cache
+            .set_bundle_signing_keys(Some(public_key_file.to_str().unwrap().to_string()))
+            .unwrap();
+        let state = hwctl_service
+            .import_response(response.to_json().unwrap())
+            .call()
+            .unwrap()
+            .state;
+        assert!(state.status == CertificationStatus::NotSeen);
+        assert!(state.source == StateSource::imported);
+        assert_eq!(state.provenance.unwrap().bundle_checksum, response.checksum);
+
+        assert!(hwctl_service
+            .import_response("{}".to_string())
+            .call()
+            .is_err());
+
         // needed to ensure that the server exits
         drop(hwctl_service);
         th.join().unwrap();
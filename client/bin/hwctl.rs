@@ -19,8 +19,9 @@
  */
 
 use anyhow::Result;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use std::process::ExitCode;
+use std::sync::Arc;
 
 extern crate serde_derive;
 
@@ -29,8 +30,10 @@ mod com_ubuntu_hwctl;
 use com_ubuntu_hwctl::VarlinkClientInterface;
 use varlink::Connection;
 
-use hwlib::constants;
+use hwlib::bundle;
+use hwlib::cache;
 use hwlib::helpers;
+use hwlib::{HttpTransport, Integration};
 
 /// CLI tool to check hardware certification status.
 ///
@@ -48,25 +51,125 @@ struct Args {
     #[arg(
         long = "server",
         env = "HW_API_URL",
-        default_value = constants::DEFAULT_SERVER_URL,
-        help = "API server URL"
+        value_delimiter = ',',
+        help = "API server URL, or comma-separated URLs to try in order. Defaults to the servers in the settings"
     )]
-    hw_api_url: String,
+    hw_api_url: Vec<String>,
     #[arg(
         long = "origin",
         default_value = "server",
-        help = "Source of the certification status information: 'auto', 'server', or 'cache'"
+        help = "Source of the certification status information: 'auto', 'server', 'cache', or 'background'"
     )]
     hw_source: String,
     #[arg(long, action = clap::ArgAction::SetTrue, help = "Allow the daemon to connect to the hardware database server")]
     enable_server_access: bool,
     #[arg(long, action = clap::ArgAction::SetTrue, help = "Prevent the daemon from connecting to the hardware database server")]
     disable_server_access: bool,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Show the latest certification checks against the server, oldest first
+    History,
+    /// Cancel the certification checks in progress
+    Cancel,
+    /// Print a request bundle with the hardware data, to check a machine
+    /// without network access from another one
+    ExportRequest,
+    /// Send a request bundle to the server and print the response bundle.
+    /// Doesn't need the daemon, so it can run on any connected machine
+    AnswerRequest {
+        /// Ed25519 private key in PEM format to sign the response bundle with
+        #[arg(long)]
+        signing_key: std::path::PathBuf,
+        /// The request bundle
+        file: std::path::PathBuf,
+    },
+    /// Store the result in a response bundle in the cache
+    ImportResponse {
+        /// The response bundle
+        file: std::path::PathBuf,
+    },
+}
+
+fn connect(socket_file: String) -> Result<com_ubuntu_hwctl::VarlinkClient> {
+    let socket_file = format!("unix://{}", socket_file);
+    let connection = Connection::with_address(&socket_file).map_err(|error| {
+        anyhow::anyhow!("Error when connecting to socket {}: {}", socket_file, error)
+    })?;
+    Ok(com_ubuntu_hwctl::VarlinkClient::new(connection))
+}
+
+fn run_history(socket_file: String) -> Result<Vec<com_ubuntu_hwctl::HistoryEntry>> {
+    let mut hwctl_service = connect(socket_file)?;
+    let reply = hwctl_service
+        .get_history()
+        .call()
+        .map_err(|error| anyhow::anyhow!("Response error: {}", error))?;
+    Ok(reply.entries)
+}
+
+fn run_cancel(socket_file: String) -> Result<bool> {
+    let mut hwctl_service = connect(socket_file)?;
+    let reply = hwctl_service
+        .cancel_check()
+        .call()
+        .map_err(|error| anyhow::anyhow!("Response error: {}", error))?;
+    Ok(reply.cancelled)
+}
+
+fn run_export_request(socket_file: String) -> Result<String> {
+    let mut hwctl_service = connect(socket_file)?;
+    let reply = hwctl_service
+        .export_request()
+        .call()
+        .map_err(|error| anyhow::anyhow!("Response error: {}", error))?;
+    Ok(reply.bundle)
+}
+
+/// Sends the request bundle to the servers in order until one of them answers.
+fn run_answer_request(
+    file: &std::path::Path,
+    signing_key: &std::path::Path,
+    server_urls: Vec<String>,
+) -> Result<String> {
+    let request = bundle::RequestBundle::from_json(&std::fs::read_to_string(file)?)?;
+    let server_urls = match server_urls.is_empty() {
+        true => cache::HWCache::new(None).get_server_urls(),
+        false => server_urls,
+    };
+    let mut error = anyhow::anyhow!("No server URL given");
+    for server_url in server_urls {
+        let transport = Arc::new(HttpTransport::new(Integration::Cli));
+        match bundle::answer_request(&request, server_url, Some(transport)) {
+            Ok(mut response) => {
+                response.sign(signing_key)?;
+                return response.to_json();
+            }
+            Err(server_error) => error = server_error,
+        }
+    }
+    Err(error)
+}
+
+fn run_import_response(
+    socket_file: String,
+    file: &std::path::Path,
+) -> Result<com_ubuntu_hwctl::State> {
+    let contents = std::fs::read_to_string(file)?;
+    let mut hwctl_service = connect(socket_file)?;
+    let reply = hwctl_service
+        .import_response(contents)
+        .call()
+        .map_err(|error| anyhow::anyhow!("Response error: {}", error))?;
+    Ok(reply.state)
 }
 
 fn run(
     socket_file: String,
-    server_url: String,
+    server_urls: Vec<String>,
     hw_source: String,
     enable_server_access: bool,
     disable_server_access: bool,
@@ -75,28 +178,16 @@ fn run(
         "server" => com_ubuntu_hwctl::CertificationSource::server,
         "cache" => com_ubuntu_hwctl::CertificationSource::cache,
         "auto" => com_ubuntu_hwctl::CertificationSource::auto,
+        "background" => com_ubuntu_hwctl::CertificationSource::background,
         _ => {
             return Err(anyhow::anyhow!(
-                "Invalid source: {}. Valid sources are 'auto', 'server' and 'cache'.",
+                "Invalid source: {}. Valid sources are 'auto', 'server', 'cache' and 'background'.",
                 hw_source
             ))
         }
     };
 
-    let socket_file = format!("unix://{}", socket_file);
-
-    let connection = Connection::with_address(&socket_file);
-
-    if connection.is_err() {
-        return Err(anyhow::anyhow!(
-            "Error when connecting to socket {}: {}",
-            socket_file,
-            connection.err().unwrap()
-        ));
-    }
-    let connection = connection.unwrap();
-
-    let mut hwctl_service = com_ubuntu_hwctl::VarlinkClient::new(connection);
+    let mut hwctl_service = connect(socket_file)?;
 
     if enable_server_access {
         let reply = hwctl_service.set_remote_access(true).call();
@@ -120,16 +211,43 @@ fn run(
         source = com_ubuntu_hwctl::CertificationSource::cache;
     }
 
+    let server_urls = Some(server_urls).filter(|urls| !urls.is_empty());
     let reply = hwctl_service
-        .get_certification_status(source, Some(server_url))
+        .get_certification_status(source, None, server_urls.clone())
         .call();
 
-    if reply.is_err() {
-        return Err(anyhow::anyhow!("Response error: {}", reply.err().unwrap()));
-    }
-    let reply = reply.unwrap().state;
+    let reply = match reply {
+        Ok(reply) => reply,
+        // The daemon reports why the server couldn't answer as an error, but
+        // the cached state, marked stale with that reason, is shown instead.
+        Err(error) if is_server_failure(&error) => hwctl_service
+            .get_certification_status(
+                com_ubuntu_hwctl::CertificationSource::cache,
+                None,
+                server_urls,
+            )
+            .call()
+            .map_err(|error| anyhow::anyhow!("Response error: {}", error))?,
+        Err(error) => return Err(anyhow::anyhow!("Response error: {}", error)),
+    };
 
-    return Ok(reply);
+    return Ok(reply.state);
+}
+
+/// Returns true if the error means that the server couldn't answer a check.
+fn is_server_failure(error: &com_ubuntu_hwctl::Error) -> bool {
+    use com_ubuntu_hwctl::ErrorKind;
+    matches!(
+        error.kind(),
+        ErrorKind::DnsError(_)
+            | ErrorKind::TlsError(_)
+            | ErrorKind::ConnectionFailed(_)
+            | ErrorKind::ServerTimeout(_)
+            | ErrorKind::RequestRejected(_)
+            | ErrorKind::ServerFailed(_)
+            | ErrorKind::RateLimited(_)
+            | ErrorKind::InvalidResponse(_)
+    )
 }
 
 fn main() -> ExitCode {
@@ -144,6 +262,34 @@ fn main() -> ExitCode {
     }
 
     let (socket_file, _) = socket_folder.unwrap();
+
+    if let Some(command) = args.command {
+        let output = match command {
+            Command::History => run_history(socket_file)
+                .map(|entries| serde_json::to_string_pretty(&entries).unwrap()),
+            Command::Cancel => run_cancel(socket_file).map(|cancelled| match cancelled {
+                true => "Cancelled the checks in progress\n".to_string(),
+                false => "No checks in progress\n".to_string(),
+            }),
+            Command::ExportRequest => run_export_request(socket_file),
+            Command::AnswerRequest { file, signing_key } => {
+                run_answer_request(&file, &signing_key, args.hw_api_url)
+            }
+            Command::ImportResponse { file } => run_import_response(socket_file, &file)
+                .map(|state| serde_json::to_string_pretty(&state).unwrap()),
+        };
+        return match output {
+            Ok(output) => {
+                print!("{}", output);
+                ExitCode::SUCCESS
+            }
+            Err(error) => {
+                eprintln!("ERROR: {}", error);
+                ExitCode::FAILURE
+            }
+        };
+    }
+
     let reply = run(
         socket_file,
         args.hw_api_url,
@@ -175,8 +321,9 @@ mod tests {
     use test_temp_dir::{test_temp_dir, TestTempDir};
 
     use crate::com_ubuntu_hwctl::{
-        Call_GetCertificationStatus, CertificationSource, CertificationStatus, State,
-        VarlinkInterface,
+        Call_CancelCheck, Call_ExportRequest, Call_GetCertificationStatus, Call_GetHistory,
+        Call_ImportResponse, CertificationSource, CertificationStatus, Compatibility, HistoryEntry,
+        Provenance, State, StateSource, VarlinkInterface,
     };
     fn keep_temp_dir_alive(_temp_dir: &TestTempDir) {
         // Keep the temp dir alive until the end of the test
@@ -187,34 +334,62 @@ mod tests {
     static mut CURRENT_REMOTE_ACCESS: bool = false;
     static mut RETURN_ERROR: bool = false;
 
+    fn fake_state(stale_reason: Option<String>) -> State {
+        State {
+            status: CertificationStatus::Certified,
+            certified_url: Some(
+                "https://certification.ubuntu.com/hardware/202308-12345".to_string(),
+            ),
+            available_releases: None,
+            valid_cache: true,
+            hardware_mismatch: false,
+            software_changed: false,
+            stale: false,
+            stale_reason,
+            source: StateSource::cache,
+            remote_access_enabled: unsafe { CURRENT_REMOTE_ACCESS },
+            server_url: "fake_url".to_string(),
+            redaction_policy: "none".to_string(),
+            hardware_fingerprint: None,
+            hardware_changes: vec![],
+            compatibility: Compatibility {
+                release_certified: false,
+                kernel_certified: false,
+                certified_flavours: vec![],
+                newer: vec![],
+                older: vec![],
+                recommended: None,
+            },
+            next_retry_at: None,
+            refresh_pending: false,
+            provenance: None,
+        }
+    }
+
     impl VarlinkInterface for ComUbuntuHwctl {
         fn get_certification_status(
             &self,
             call: &mut dyn Call_GetCertificationStatus,
             r#source: CertificationSource,
             r#server_url: Option<String>,
+            r#server_urls: Option<Vec<String>>,
         ) -> varlink::Result<()> {
             if unsafe { RETURN_ERROR } {
                 return call.reply_cannot_collect_system_data();
             }
-            let response_varlink = State {
-                status: CertificationStatus::Certified,
-                certified_url: Some(
-                    "https://certification.ubuntu.com/hardware/202308-12345".to_string(),
-                ),
-                available_releases: None,
-                valid_cache: true,
-                hardware_mismatch: false,
-                stale: false,
-                stale_reason: server_url,
-                source: match r#source {
-                    CertificationSource::cache => CertificationSource::server,
-                    CertificationSource::server => CertificationSource::auto,
-                    CertificationSource::auto => CertificationSource::cache,
-                },
-                remote_access_enabled: unsafe { CURRENT_REMOTE_ACCESS },
-                server_url: "fake_url".to_string(),
+            let server_urls = r#server_urls.map(|server_urls| server_urls.join(","));
+            // A server that can't be reached, whose failure is left in the cache.
+            let server_down = server_urls.as_deref() == Some("down_url");
+            if server_down && r#source == CertificationSource::server {
+                return call.reply_connection_failed("connection refused".to_string());
+            }
+            let mut response_varlink = fake_state(server_urls.or(r#server_url));
+            response_varlink.stale = server_down;
+            response_varlink.source = match r#source {
+                CertificationSource::cache | CertificationSource::server => StateSource::server,
+                CertificationSource::auto | CertificationSource::background => StateSource::cache,
             };
+            response_varlink.refresh_pending = r#source == CertificationSource::background;
             return call.reply(response_varlink);
         }
 
@@ -231,6 +406,57 @@ mod tests {
             }
             return call.reply();
         }
+
+        fn get_history(&self, call: &mut dyn Call_GetHistory) -> varlink::Result<()> {
+            if unsafe { RETURN_ERROR } {
+                return call.reply_cannot_collect_system_data();
+            }
+            call.reply(vec![HistoryEntry {
+                timestamp: "2026-01-01T00:00:00+00:00".to_string(),
+                server: "fake_url".to_string(),
+                hardware_fingerprint: None,
+                status: CertificationStatus::NotSeen,
+                stale: false,
+                stale_reason: None,
+                latency_ms: 120,
+            }])
+        }
+
+        fn cancel_check(&self, call: &mut dyn Call_CancelCheck) -> varlink::Result<()> {
+            if unsafe { RETURN_ERROR } {
+                return call.reply_cannot_collect_system_data();
+            }
+            call.reply(true)
+        }
+
+        fn export_request(&self, call: &mut dyn Call_ExportRequest) -> varlink::Result<()> {
+            if unsafe { RETURN_ERROR } {
+                return call.reply_cannot_collect_system_data();
+            }
+            call.reply("fake_bundle".to_string())
+        }
+
+        fn import_response(
+            &self,
+            call: &mut dyn Call_ImportResponse,
+            r#bundle: String,
+        ) -> varlink::Result<()> {
+            if unsafe { RETURN_ERROR } {
+                return call.reply_cannot_collect_system_data();
+            }
+            if r#bundle != "fake_response" {
+                return call.reply_invalid_bundle("not a bundle".to_string());
+            }
+            let mut response_varlink = fake_state(None);
+            response_varlink.source = StateSource::imported;
+            response_varlink.provenance = Some(Provenance {
+                server: "fake_url".to_string(),
+                answered_at: "2026-01-01T00:00:00+00:00".to_string(),
+                imported_at: "2026-01-02T00:00:00+00:00".to_string(),
+                bundle_checksum: "sha256:00".to_string(),
+            });
+            call.reply(response_varlink)
+        }
     }
 
     fn create_fake_server(socket_path: String) {
@@ -278,7 +504,7 @@ mod tests {
 
         let result = run(
             socket_path.clone(),
-            "fake_url".to_string(),
+            vec!["fake_url".to_string()],
             "invalid option".to_string(),
             false,
             false,
@@ -287,7 +513,7 @@ mod tests {
 
         let result = run(
             socket_path.clone(),
-            "fake_url".to_string(),
+            vec!["fake_url".to_string()],
             "auto".to_string(),
             false,
             false,
@@ -304,12 +530,34 @@ mod tests {
         assert!(!result.hardware_mismatch);
         assert!(!result.stale);
         assert!(result.stale_reason == Some("fake_url".to_string()));
-        assert!(result.source == CertificationSource::cache);
+        assert!(result.source == StateSource::cache);
         assert!(!result.remote_access_enabled);
 
+        // All the servers are sent, in order.
+        let result = run(
+            socket_path.clone(),
+            vec!["mirror_url".to_string(), "fake_url".to_string()],
+            "auto".to_string(),
+            false,
+            false,
+        )
+        .unwrap();
+        assert_eq!(result.stale_reason, Some("mirror_url,fake_url".to_string()));
+
+        // Without servers, the daemon asks the ones in its settings.
+        let result = run(
+            socket_path.clone(),
+            vec![],
+            "auto".to_string(),
+            false,
+            false,
+        )
+        .unwrap();
+        assert_eq!(result.stale_reason, None);
+
         let result = run(
             socket_path.clone(),
-            "other_fake_url".to_string(),
+            vec!["other_fake_url".to_string()],
             "server".to_string(),
             true,
             false,
@@ -326,12 +574,12 @@ mod tests {
         assert!(!result.hardware_mismatch);
         assert!(!result.stale);
         assert!(result.stale_reason == Some("other_fake_url".to_string()));
-        assert!(result.source == CertificationSource::server);
+        assert!(result.source == StateSource::server);
         assert!(result.remote_access_enabled);
 
         let result = run(
             socket_path.clone(),
-            "other_fake_url".to_string(),
+            vec!["other_fake_url".to_string()],
             "server".to_string(),
             false,
             true,
@@ -348,16 +596,57 @@ mod tests {
         assert!(!result.hardware_mismatch);
         assert!(!result.stale);
         assert!(result.stale_reason == Some("other_fake_url".to_string()));
-        assert!(result.source == CertificationSource::server);
+        assert!(result.source == StateSource::server);
         assert!(!result.remote_access_enabled);
 
+        let result = run(
+            socket_path.clone(),
+            vec!["fake_url".to_string()],
+            "background".to_string(),
+            false,
+            false,
+        )
+        .unwrap();
+        assert!(result.source == StateSource::cache);
+        assert!(result.refresh_pending);
+
+        // The stale cached state is shown when the server can't answer.
+        let result = run(
+            socket_path.clone(),
+            vec!["down_url".to_string()],
+            "server".to_string(),
+            false,
+            false,
+        )
+        .unwrap();
+        assert!(result.stale);
+        assert!(result.stale_reason == Some("down_url".to_string()));
+
+        let history = run_history(socket_path.clone()).unwrap();
+        assert_eq!(history.len(), 1);
+        assert!(history[0].status == CertificationStatus::NotSeen);
+
+        assert_eq!(
+            run_export_request(socket_path.clone()).unwrap(),
+            "fake_bundle"
+        );
+        let response_path = temp_dir.as_path_untracked().join("response.json");
+        std::fs::write(&response_path, "fake_response").unwrap();
+        let result = run_import_response(socket_path.clone(), &response_path).unwrap();
+        assert!(result.source == StateSource::imported);
+        assert!(result.provenance.is_some());
+        std::fs::write(&response_path, "{}").unwrap();
+        assert!(run_import_response(socket_path.clone(), &response_path).is_err());
+
         unsafe {
             RETURN_ERROR = true;
         }
 
+        assert!(run_history(socket_path.clone()).is_err());
+
         let result = run(
             socket_path.clone(),
-            "other_fake_url".to_string(),
+            vec!["other_fake_url".to_string()],
             "server".to_string(),
             false,
             true,
@@ -366,7 +655,7 @@ mod tests {
 
         let result = run(
             socket_path.clone(),
-            "other_fake_url".to_string(),
+            vec!["other_fake_url".to_string()],
             "server".to_string(),
             true,
             false,
@@ -375,7 +664,7 @@ mod tests {
 
         let result = run(
             socket_path.clone(),
-            "other_fake_url".to_string(),
+            vec!["other_fake_url".to_string()],
             "server".to_string(),
             false,
             false,
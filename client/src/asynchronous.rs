@@ -0,0 +1,219 @@
+/* Copyright 2026 Canonical Ltd.
+ *
+ * This program is free software: you can redistribute it and/or
+ * modify it under the terms of the GNU Lesser General Public License
+ * version 3, as published by the Free Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Lesser General Public License for more details.
+ *
+ * You should have received a copy of the GNU Lesser General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Async variants of the certification checks, available with the `async`
+//! feature.
+//!
+//! They don't depend on any runtime: the blocking checks run in their own
+//! thread, so they have the same cache semantics, and the returned futures
+//! complete when they end. Dropping a future cancels its check, leaving the
+//! cache as it was.
+
+use anyhow::{anyhow, Result};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::cache::HWCache;
+use crate::models::request_validators::{CertificationStatusRequest, Paths};
+use crate::{
+    check_certification_status_failover, CancellationToken, CheckCertificationSource,
+    PublicCertificationStatus, Transport,
+};
+
+/// Cancels the token when dropped.
+struct CancelOnDrop(CancellationToken);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.cancel();
+    }
+}
+
+/// Runs `work` in a new thread, completing with its result. The token passed
+/// to `work` is cancelled if the future is dropped before.
+async fn run_blocking<T: Send + 'static>(
+    work: impl FnOnce(&CancellationToken) -> Result<T> + Send + 'static,
+) -> Result<T> {
+    let cancel = CancellationToken::new();
+    let _guard = CancelOnDrop(cancel.clone());
+    let (sender, receiver) = futures_channel::oneshot::channel();
+    std::thread::spawn(move || {
+        let _ = sender.send(work(&cancel));
+    });
+    receiver
+        .await
+        .unwrap_or_else(|_| Err(anyhow!("The certification check was interrupted")))
+}
+
+/// Returns the certification status of the hardware according to `mode`,
+/// asking the servers in `servers` in order, like
+/// `check_certification_status_failover`.
+///
+/// cache_folder: Where the cache is, or None for the default location.
+/// transport_opt: How to ask the servers, or None to use HTTP.
+///
+/// In `Background` mode, the answer has `refresh_pending` set if the cache
+/// must be refreshed, which is done by awaiting another check in `Auto` mode.
+pub async fn check_certification_status(
+    servers: Vec<String>,
+    mode: CheckCertificationSource,
+    hardware_info: CertificationStatusRequest,
+    cache_folder: Option<PathBuf>,
+    transport_opt: Option<Arc<dyn Transport>>,
+) -> Result<PublicCertificationStatus> {
+    run_blocking(move |cancel| {
+        let mut cache = HWCache::new(cache_folder.as_deref());
+        check_certification_status_failover(
+            &servers,
+            mode,
+            &hardware_info,
+            Some(&mut cache),
+            transport_opt,
+            cancel,
+        )
+    })
+    .await
+}
+
+/// Collects the hardware data from `paths` and returns its certification
+/// status, like `check_certification_status`.
+pub async fn collect_and_check_certification_status(
+    servers: Vec<String>,
+    mode: CheckCertificationSource,
+    paths: Paths,
+    cache_folder: Option<PathBuf>,
+    transport_opt: Option<Arc<dyn Transport>>,
+) -> Result<PublicCertificationStatus> {
+    let hardware_info = run_blocking(move |_| CertificationStatusRequest::new(paths)).await?;
+    check_certification_status(servers, mode, hardware_info, cache_folder, transport_opt).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::{CertificationStatus, StaleStatus};
+    use crate::helpers::test_utils::{answering, certified, MockTransport};
+    use crate::models::devices::{Board, Processor};
+    use crate::models::response_validators::CertificationStatusResponse;
+    use crate::models::software::{KernelPackage, OS};
+    use crate::{CertificationSource, TransportResponse};
+    use std::future::Future;
+    use std::time::{Duration, Instant};
+    use test_temp_dir::test_temp_dir;
+
+    const SERVER_URL: &str = "https://hw.example.com";
+
+    fn create_test_hardware_data() -> CertificationStatusRequest {
+        CertificationStatusRequest {
+            architecture: "x86_64".to_string(),
+            bios: None,
+            board: Board::default(),
+            chassis: None,
+            model: "".to_string(),
+            os: OS {
+                codename: "".to_string(),
+                distributor: "".to_string(),
+                version: "".to_string(),
+                kernel: KernelPackage {
+                    name: None,
+                    version: "".to_string(),
+                    signature: None,
+                    loaded_modules: vec![],
+                },
+            },
+            pci_peripherals: vec![],
+            processor: Processor {
+                identifier: None,
+                frequency: 0,
+                version: "".to_string(),
+                manufacturer: "".to_string(),
+            },
+            usb_peripherals: vec![],
+            vendor: "".to_string(),
+        }
+    }
+
+    fn assert_send<T: Send>(_: &T) {}
+
+    #[test]
+    fn test_async_check_uses_the_cache() {
+        let temp_dir = test_temp_dir!();
+        let folder = temp_dir.as_path_untracked().to_path_buf();
+        let mut cache = HWCache::new(Some(&folder));
+        cache.set_remote_access_enabled(true).unwrap();
+        cache.set_allow_custom_url_enabled(true).unwrap();
+
+        let check = |mode| {
+            check_certification_status(
+                vec![SERVER_URL.to_string()],
+                mode,
+                create_test_hardware_data(),
+                Some(folder.clone()),
+                answering(certified()),
+            )
+        };
+        let future = check(CheckCertificationSource::Auto);
+        // It can be spawned on multi-threaded runtimes.
+        assert_send(&future);
+        let data = futures_executor::block_on(future).unwrap();
+        assert_eq!(data.status, CertificationStatus::Certified);
+        assert_eq!(data.source, CertificationSource::Server);
+
+        let data = futures_executor::block_on(check(CheckCertificationSource::Auto)).unwrap();
+        assert_eq!(data.status, CertificationStatus::Certified);
+        assert_eq!(data.source, CertificationSource::Cache);
+    }
+
+    #[test]
+    fn test_dropping_the_future_cancels_the_check() {
+        let temp_dir = test_temp_dir!();
+        let folder = temp_dir.as_path_untracked().to_path_buf();
+        let mut cache = HWCache::new(Some(&folder));
+        cache.set_remote_access_enabled(true).unwrap();
+        cache.set_allow_custom_url_enabled(true).unwrap();
+        let stale = || HWCache::new(Some(&folder)).get_status().2;
+
+        let mut future = Box::pin(check_certification_status(
+            vec![SERVER_URL.to_string()],
+            CheckCertificationSource::Server,
+            create_test_hardware_data(),
+            Some(folder.clone()),
+            Some(Arc::new(MockTransport {
+                result: Ok(TransportResponse {
+                    response: Some(CertificationStatusResponse::NotSeen),
+                    max_age: None,
+                    etag: None,
+                }),
+                delay: Duration::from_secs(10),
+            })),
+        ));
+        let mut context = std::task::Context::from_waker(std::task::Waker::noop());
+        assert!(future.as_mut().poll(&mut context).is_pending());
+        let started = Instant::now();
+        while stale() != StaleStatus::Connecting {
+            assert!(started.elapsed() < Duration::from_secs(5));
+            std::thread::sleep(Duration::from_millis(50));
+        }
+
+        drop(future);
+        // The previous status is restored once the check notices it.
+        while stale() == StaleStatus::Connecting {
+            assert!(started.elapsed() < Duration::from_secs(5));
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        assert_eq!(stale(), StaleStatus::Valid);
+        assert!(HWCache::new(Some(&folder)).get_history().is_empty());
+    }
+}
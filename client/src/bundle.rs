@@ -0,0 +1,526 @@
+/* Copyright 2026 Canonical Ltd.
+ *
+ * This program is free software: you can redistribute it and/or
+ * modify it under the terms of the GNU Lesser General Public License
+ * version 3, as published by the Free Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Lesser General Public License for more details.
+ *
+ * You should have received a copy of the GNU Lesser General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Bundles to check machines without network access.
+//!
+//! The offline machine exports its hardware data in a request bundle, a
+//! connected machine sends it to the server and writes the answer in a
+//! response bundle, and the offline machine imports it into its cache.
+//!
+//! Every bundle carries a SHA-256 checksum of its contents, and a response
+//! is bound to the fingerprint of the hardware it was requested for. This
+//! detects damaged bundles and answers meant for another machine. Response
+//! bundles are also signed with an Ed25519 key of the operator, and only
+//! imported if the key is among the trusted ones of the offline machine, so
+//! that nobody else can make it report a status.
+
+use anyhow::{anyhow, Context, Result};
+use openssl::{
+    pkey::{PKey, Public},
+    sign::{Signer, Verifier},
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{path::Path, sync::Arc};
+
+use crate::{
+    cache::HWCache,
+    constants::{self, CERT_STATUS_ENDPOINT},
+    create_answer, helpers, interpret_response,
+    models::{
+        request_validators::CertificationStatusRequest,
+        response_validators::CertificationStatusResponse,
+    },
+    storage,
+    transport::{ConnectionSettings, HttpTransport, Transport, TransportResponse},
+    CertificationSource, CustomUrlNotAllowed, PublicCertificationStatus,
+};
+
+pub const BUNDLE_FORMAT_VERSION: u32 = 1;
+
+/// Where an imported result comes from.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Provenance {
+    /// The server that answered.
+    pub server: String,
+    /// When the server answered, in RFC 3339 format.
+    pub answered_at: String,
+    /// When the result was imported, in RFC 3339 format.
+    pub imported_at: String,
+    /// Checksum of the imported response bundle.
+    pub bundle_checksum: String,
+}
+
+/// The hardware data of a machine, to be sent to the server from another one.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RequestBundle {
+    pub format_version: u32,
+    pub created_at: String,
+    pub hardware_fingerprint: String,
+    pub request: CertificationStatusRequest,
+    pub checksum: String,
+}
+
+/// The signature of a response bundle.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BundleSignature {
+    /// SHA-256 digest of the SubjectPublicKeyInfo of the signing key, in the
+    /// `sha256:<hex>` format.
+    pub key: String,
+    /// Ed25519 signature of the bundle checksum, in hex.
+    pub signature: String,
+}
+
+/// The answer of the server to a request bundle.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ResponseBundle {
+    pub format_version: u32,
+    /// When the server answered, in RFC 3339 format.
+    pub created_at: String,
+    pub server: String,
+    pub hardware_fingerprint: String,
+    /// Checksum of the request bundle that was answered.
+    pub request_checksum: String,
+    /// How many seconds the server allows the answer to be cached, if it said so.
+    pub max_age: Option<u64>,
+    pub response: CertificationStatusResponse,
+    pub checksum: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<BundleSignature>,
+}
+
+/// Returns the checksum of a bundle, which covers every field but `checksum`
+/// and `signature`.
+fn checksum<T: Serialize>(bundle: &T) -> Result<String> {
+    let mut value = serde_json::to_value(bundle)?;
+    if let Some(fields) = value.as_object_mut() {
+        fields.remove("checksum");
+        fields.remove("signature");
+    }
+    let digest = Sha256::digest(serde_json::to_vec(&value)?);
+    Ok(format!("sha256:{}", helpers::to_hex(&digest)))
+}
+
+/// Parses a bundle, checking its format version and its checksum.
+fn parse_bundle<T: Serialize + DeserializeOwned>(
+    json: &str,
+    format_version: impl Fn(&T) -> u32,
+    stored_checksum: impl Fn(&T) -> &str,
+) -> Result<T> {
+    let bundle: T = serde_json::from_str(json)?;
+    if format_version(&bundle) > BUNDLE_FORMAT_VERSION {
+        return Err(anyhow!(
+            "Unsupported bundle format version: {}",
+            format_version(&bundle)
+        ));
+    }
+    if checksum(&bundle)? != stored_checksum(&bundle) {
+        return Err(anyhow!("The bundle is damaged: its checksum doesn't match"));
+    }
+    Ok(bundle)
+}
+
+impl RequestBundle {
+    pub fn from_json(json: &str) -> Result<Self> {
+        let bundle: Self = parse_bundle(json, |b: &Self| b.format_version, |b| &b.checksum)?;
+        if bundle.request.fingerprint() != bundle.hardware_fingerprint {
+            return Err(anyhow!(
+                "The bundle fingerprint doesn't match its hardware data"
+            ));
+        }
+        Ok(bundle)
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+impl ResponseBundle {
+    /// Creates the bundle for the answer of `server` to a request, which it
+    /// sent just now.
+    pub fn new(
+        request: &RequestBundle,
+        server: String,
+        response: CertificationStatusResponse,
+        max_age: Option<u64>,
+    ) -> Result<Self> {
+        let mut bundle = ResponseBundle {
+            format_version: BUNDLE_FORMAT_VERSION,
+            created_at: chrono::Utc::now().to_rfc3339(),
+            server,
+            hardware_fingerprint: request.hardware_fingerprint.clone(),
+            request_checksum: request.checksum.clone(),
+            max_age,
+            response,
+            checksum: String::new(),
+            signature: None,
+        };
+        bundle.checksum = checksum(&bundle)?;
+        Ok(bundle)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        parse_bundle(json, |b: &Self| b.format_version, |b| &b.checksum)
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Signs the bundle with the Ed25519 private key in PEM format at
+    /// `key_file`, which must be accessible only by its owner.
+    pub fn sign(&mut self, key_file: &Path) -> Result<()> {
+        let pem = storage::read_protected_file(key_file, true)?;
+        let key = PKey::private_key_from_pem(&pem)
+            .with_context(|| format!("invalid private key in {}", key_file.display()))?;
+        let signature = Signer::new_without_digest(&key)
+            .and_then(|mut signer| signer.sign_oneshot_to_vec(self.checksum.as_bytes()))
+            .context("cannot sign the bundle, the key must be an Ed25519 one")?;
+        self.signature = Some(BundleSignature {
+            key: key_id(&key.public_key_to_der()?),
+            signature: helpers::to_hex(&signature),
+        });
+        Ok(())
+    }
+
+    /// Checks that the bundle is signed by one of the Ed25519 public keys in
+    /// the PEM file at `keys_file`, which must not be writable by other users.
+    fn verify_signature(&self, keys_file: &Path) -> Result<()> {
+        let signature = self
+            .signature
+            .as_ref()
+            .ok_or_else(|| anyhow!("The bundle isn't signed"))?;
+        let key = load_public_keys(keys_file)?
+            .into_iter()
+            .find(|key| {
+                key.public_key_to_der()
+                    .is_ok_and(|der| key_id(&der) == signature.key)
+            })
+            .ok_or_else(|| anyhow!("The bundle isn't signed by a trusted key"))?;
+        let valid = helpers::from_hex(&signature.signature).is_some_and(|bytes| {
+            Verifier::new_without_digest(&key)
+                .and_then(|mut verifier| verifier.verify_oneshot(&bytes, self.checksum.as_bytes()))
+                .unwrap_or(false)
+        });
+        if !valid {
+            return Err(anyhow!("The bundle signature is invalid"));
+        }
+        Ok(())
+    }
+}
+
+/// Returns the identifier of a public key from its SubjectPublicKeyInfo in DER.
+fn key_id(der: &[u8]) -> String {
+    format!("sha256:{}", helpers::to_hex(&Sha256::digest(der)))
+}
+
+fn load_public_keys(path: &Path) -> Result<Vec<PKey<Public>>> {
+    let pem = storage::read_protected_file(path, false)?;
+    let pem = String::from_utf8(pem)
+        .with_context(|| format!("invalid public keys in {}", path.display()))?;
+    const BEGIN: &str = "-----BEGIN PUBLIC KEY-----";
+    pem.split(BEGIN)
+        .skip(1)
+        .map(|block| {
+            PKey::public_key_from_pem(format!("{}{}", BEGIN, block).as_bytes())
+                .with_context(|| format!("invalid public keys in {}", path.display()))
+        })
+        .collect()
+}
+
+/// Creates a request bundle with the hardware data, redacted for the server
+/// with the redaction policy of the cache.
+pub fn export_request(
+    hardware_info: &CertificationStatusRequest,
+    cache_opt: Option<&HWCache>,
+) -> Result<RequestBundle> {
+    let local_cache;
+    let cache = match cache_opt {
+        Some(cache) => cache,
+        None => {
+            local_cache = HWCache::new(None);
+            &local_cache
+        }
+    };
+    let request = cache.get_redaction_policy().apply_for_server(hardware_info);
+    let mut bundle = RequestBundle {
+        format_version: BUNDLE_FORMAT_VERSION,
+        created_at: chrono::Utc::now().to_rfc3339(),
+        hardware_fingerprint: request.fingerprint(),
+        request,
+        checksum: String::new(),
+    };
+    bundle.checksum = checksum(&bundle)?;
+    Ok(bundle)
+}
+
+/// Sends the hardware data of a request bundle to the server at `url` through
+/// `transport_opt`, or over HTTP if None, and returns its answer as a
+/// response bundle.
+pub fn answer_request(
+    bundle: &RequestBundle,
+    url: String,
+    transport_opt: Option<Arc<dyn Transport>>,
+) -> Result<ResponseBundle> {
+    let mut server_url = url.clone();
+    server_url.push_str(CERT_STATUS_ENDPOINT);
+    // Only the settings of this machine, e.g. its proxy, are used.
+    let connection = ConnectionSettings::new(&server_url, &HWCache::new(None));
+    let transport = transport_opt.unwrap_or_else(|| Arc::new(HttpTransport::default()));
+    let TransportResponse {
+        response, max_age, ..
+    } = transport.send(&server_url, &bundle.request, &connection)?;
+    // No ETag is sent, so the server must answer in full.
+    let response = response
+        .ok_or_else(|| anyhow!("The server answered Not Modified to a request without ETag"))?;
+    ResponseBundle::new(bundle, url, response, max_age)
+}
+
+/// Stores the answer in a response bundle in the cache, after checking that
+/// it is signed by a trusted key and was obtained for the current hardware.
+pub fn import_response(
+    bundle: &ResponseBundle,
+    hardware_info: &CertificationStatusRequest,
+    cache_opt: Option<&mut HWCache>,
+) -> Result<PublicCertificationStatus> {
+    let mut local_cache = HWCache::new(None);
+    let cache: &mut HWCache = match cache_opt {
+        Some(cache) => cache,
+        None => &mut local_cache,
+    };
+
+    let keys_file = cache
+        .get_bundle_signing_keys()
+        .ok_or_else(|| anyhow!("No keys are trusted to sign bundles"))?;
+    // The checksum is checked again, as the bundle may not come from JSON.
+    if checksum(bundle)? != bundle.checksum {
+        return Err(anyhow!("The bundle is damaged: its checksum doesn't match"));
+    }
+    bundle.verify_signature(Path::new(keys_file))?;
+    if bundle.server != constants::DEFAULT_SERVER_URL && !cache.get_allow_custom_url_enabled() {
+        return Err(CustomUrlNotAllowed.into());
+    }
+    let policy = cache.get_redaction_policy();
+    if policy.apply_for_server(hardware_info).fingerprint() != bundle.hardware_fingerprint {
+        return Err(anyhow!("The bundle was obtained for different hardware"));
+    }
+    let hardware_info = policy.apply(hardware_info);
+
+    let (status, certified_url, available_releases) = interpret_response(&bundle.response);
+    cache.import_certification(
+        &hardware_info,
+        status,
+        certified_url,
+        available_releases,
+        bundle.max_age,
+        Provenance {
+            server: bundle.server.clone(),
+            answered_at: bundle.created_at.clone(),
+            imported_at: chrono::Utc::now().to_rfc3339(),
+            bundle_checksum: bundle.checksum.clone(),
+        },
+    )?;
+    Ok(create_answer(
+        cache,
+        CertificationSource::Imported,
+        &hardware_info,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::test_utils::{answering, certified};
+    use crate::{
+        cache::CertificationStatus,
+        check_certification_status,
+        models::{
+            devices::{Board, Processor},
+            software::{KernelPackage, OS},
+        },
+        CheckCertificationSource,
+    };
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::PathBuf;
+    use test_temp_dir::test_temp_dir;
+
+    const SERVER_URL: &str = "https://hw.example.com";
+
+    /// Writes a new signing key and the file with its public key in `dir`,
+    /// and returns their paths.
+    fn create_signing_key(dir: &Path, name: &str) -> (PathBuf, PathBuf) {
+        let key = PKey::generate_ed25519().unwrap();
+        let key_file = dir.join(format!("{}.pem", name));
+        let public_key_file = dir.join(format!("{}.pub", name));
+        storage::write_file_atomically(&key_file, &key.private_key_to_pem_pkcs8().unwrap(), 0o600)
+            .unwrap();
+        storage::write_file_atomically(&public_key_file, &key.public_key_to_pem().unwrap(), 0o644)
+            .unwrap();
+        (key_file, public_key_file)
+    }
+
+    fn create_test_hardware_data(model: &str) -> CertificationStatusRequest {
+        CertificationStatusRequest {
+            architecture: "amd64".to_string(),
+            bios: None,
+            board: Board::default(),
+            chassis: None,
+            model: model.to_string(),
+            os: OS {
+                codename: "noble".to_string(),
+                distributor: "Ubuntu".to_string(),
+                version: "24.04".to_string(),
+                kernel: KernelPackage {
+                    name: None,
+                    version: "6.8.0-31-generic".to_string(),
+                    signature: None,
+                    loaded_modules: vec![],
+                },
+            },
+            pci_peripherals: vec![],
+            processor: Processor {
+                identifier: None,
+                frequency: 0,
+                version: "".to_string(),
+                manufacturer: "".to_string(),
+            },
+            usb_peripherals: vec![],
+            vendor: "".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_offline_round_trip() {
+        let temp_dir = test_temp_dir!();
+        let (key_file, public_key_file) =
+            create_signing_key(temp_dir.as_path_untracked(), "operator");
+        let mut cache = HWCache::new(Some(temp_dir.as_path_untracked()));
+        cache.set_allow_custom_url_enabled(true).unwrap();
+        cache
+            .set_bundle_signing_keys(Some(public_key_file.to_str().unwrap().to_string()))
+            .unwrap();
+        let hardware_info = create_test_hardware_data("XPS 13");
+
+        let request = export_request(&hardware_info, Some(&cache)).unwrap();
+        let request = RequestBundle::from_json(&request.to_json().unwrap()).unwrap();
+        let mut response =
+            answer_request(&request, SERVER_URL.to_string(), answering(certified())).unwrap();
+        response.sign(&key_file).unwrap();
+        let response = ResponseBundle::from_json(&response.to_json().unwrap()).unwrap();
+        assert_eq!(response.request_checksum, request.checksum);
+
+        let data = import_response(&response, &hardware_info, Some(&mut cache)).unwrap();
+        assert_eq!(data.status, CertificationStatus::Certified);
+        assert_eq!(data.source, CertificationSource::Imported);
+        let provenance = data.provenance().unwrap();
+        assert_eq!(provenance.server, SERVER_URL);
+        assert_eq!(provenance.bundle_checksum, response.checksum);
+
+        // The imported result is reported from the cache from now on.
+        let mut cache = HWCache::new(Some(temp_dir.as_path_untracked()));
+        let data = check_certification_status(
+            SERVER_URL.to_string(),
+            CheckCertificationSource::Cache,
+            &hardware_info,
+            Some(&mut cache),
+        )
+        .unwrap();
+        assert_eq!(data.status, CertificationStatus::Certified);
+        assert_eq!(data.source, CertificationSource::Imported);
+        assert_eq!(data.provenance(), Some(provenance));
+    }
+
+    #[test]
+    fn test_damaged_bundles_are_rejected() {
+        let hardware_info = create_test_hardware_data("XPS 13");
+        let temp_dir = test_temp_dir!();
+        let cache = HWCache::new(Some(temp_dir.as_path_untracked()));
+        let json = export_request(&hardware_info, Some(&cache))
+            .unwrap()
+            .to_json()
+            .unwrap();
+        assert!(RequestBundle::from_json(&json.replace("XPS 13", "XPS 15")).is_err());
+
+        let request = RequestBundle::from_json(&json).unwrap();
+        let json = answer_request(&request, SERVER_URL.to_string(), answering(certified()))
+            .unwrap()
+            .to_json()
+            .unwrap();
+        assert!(ResponseBundle::from_json(&json.replace("Certified", "NotSeen")).is_err());
+    }
+
+    #[test]
+    fn test_unsigned_responses_are_rejected() {
+        let temp_dir = test_temp_dir!();
+        let (key_file, public_key_file) =
+            create_signing_key(temp_dir.as_path_untracked(), "operator");
+        let (other_key_file, _) = create_signing_key(temp_dir.as_path_untracked(), "other");
+        let mut cache = HWCache::new(Some(temp_dir.as_path_untracked()));
+        cache.set_allow_custom_url_enabled(true).unwrap();
+        let hardware_info = create_test_hardware_data("XPS 13");
+
+        let request = export_request(&hardware_info, Some(&cache)).unwrap();
+        let mut response =
+            answer_request(&request, SERVER_URL.to_string(), answering(certified())).unwrap();
+        response.sign(&key_file).unwrap();
+        // No key is trusted until the operator configures them.
+        assert!(import_response(&response, &hardware_info, Some(&mut cache)).is_err());
+
+        cache
+            .set_bundle_signing_keys(Some(public_key_file.to_str().unwrap().to_string()))
+            .unwrap();
+        let mut unsigned = ResponseBundle::from_json(&response.to_json().unwrap()).unwrap();
+        unsigned.signature = None;
+        let mut untrusted = ResponseBundle::from_json(&response.to_json().unwrap()).unwrap();
+        untrusted.sign(&other_key_file).unwrap();
+        let mut forged = ResponseBundle::from_json(&response.to_json().unwrap()).unwrap();
+        forged.signature.as_mut().unwrap().signature = "00".repeat(64);
+        for bundle in [unsigned, untrusted, forged] {
+            assert!(import_response(&bundle, &hardware_info, Some(&mut cache)).is_err());
+        }
+        assert_eq!(cache.get_status().0, CertificationStatus::Unknown);
+
+        // Nor is a key other users can replace.
+        std::fs::set_permissions(&public_key_file, std::fs::Permissions::from_mode(0o666)).unwrap();
+        assert!(import_response(&response, &hardware_info, Some(&mut cache)).is_err());
+        std::fs::set_permissions(&public_key_file, std::fs::Permissions::from_mode(0o644)).unwrap();
+        import_response(&response, &hardware_info, Some(&mut cache)).unwrap();
+    }
+
+    #[test]
+    fn test_response_for_other_hardware_is_rejected() {
+        let temp_dir = test_temp_dir!();
+        let (key_file, public_key_file) =
+            create_signing_key(temp_dir.as_path_untracked(), "operator");
+        let mut cache = HWCache::new(Some(temp_dir.as_path_untracked()));
+        cache.set_allow_custom_url_enabled(true).unwrap();
+        cache
+            .set_bundle_signing_keys(Some(public_key_file.to_str().unwrap().to_string()))
+            .unwrap();
+
+        let request = export_request(&create_test_hardware_data("XPS 13"), Some(&cache)).unwrap();
+        let mut response =
+            answer_request(&request, SERVER_URL.to_string(), answering(certified())).unwrap();
+        response.sign(&key_file).unwrap();
+        let result = import_response(
+            &response,
+            &create_test_hardware_data("XPS 15"),
+            Some(&mut cache),
+        );
+        assert!(result.is_err());
+        assert_eq!(cache.get_status().0, CertificationStatus::Unknown);
+    }
+}
@@ -0,0 +1,256 @@
+/* Copyright 2026 Canonical Ltd.
+ *
+ * This program is free software: you can redistribute it and/or
+ * modify it under the terms of the GNU Lesser General Public License
+ * version 3, as published by the Free Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Lesser General Public License for more details.
+ *
+ * You should have received a copy of the GNU Lesser General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::cmp::Ordering;
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::software::OS;
+
+/// How the running OS compares with the releases the system is certified for.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct ReleaseCompatibility {
+    /// The running distributor and release are among the certified ones.
+    pub release_certified: bool,
+    /// The running release and kernel version are a certified combination.
+    pub kernel_certified: bool,
+    /// Kernel flavours (e.g. `generic` or `oem`) certified for the running release.
+    pub certified_flavours: Vec<String>,
+    /// Certified combinations with a newer kernel than the running one.
+    pub newer: Vec<OS>,
+    /// Certified combinations with an older kernel than the running one.
+    pub older: Vec<OS>,
+    /// The certified combination closest to the running one, if any.
+    pub recommended: Option<OS>,
+}
+
+/// A kernel version such as `6.8.0-1013-oem`, split into its numeric part,
+/// `[6, 8, 0, 1013]`, and its flavour, `oem`.
+#[derive(Debug, PartialEq, Eq)]
+struct KernelVersion {
+    numbers: Vec<u64>,
+    flavour: Option<String>,
+}
+
+impl KernelVersion {
+    fn parse(version: &str) -> Self {
+        let mut numbers = Vec::new();
+        let mut tokens = version.split(['.', '-']).peekable();
+        while let Some(number) = tokens.peek().and_then(|token| token.parse::<u64>().ok()) {
+            numbers.push(number);
+            tokens.next();
+        }
+        let flavour = tokens.collect::<Vec<&str>>().join("-");
+        KernelVersion {
+            numbers,
+            flavour: if flavour.is_empty() {
+                None
+            } else {
+                Some(flavour)
+            },
+        }
+    }
+}
+
+/// Converts a release version such as `24.04` into a number that preserves the
+/// ordering and the distance between releases.
+fn release_number(version: &str) -> u64 {
+    let mut parts = version
+        .split('.')
+        .map(|part| part.parse::<u64>().unwrap_or(0));
+    let major = parts.next().unwrap_or(0);
+    let minor = parts.next().unwrap_or(0);
+    major * 100 + minor
+}
+
+fn same_release(running: &OS, certified: &OS) -> bool {
+    running
+        .distributor
+        .eq_ignore_ascii_case(&certified.distributor)
+        && running.version == certified.version
+}
+
+/// Compares the running OS with the certified releases.
+pub fn check_compatibility(running: &OS, available_releases: &[OS]) -> ReleaseCompatibility {
+    let running_kernel = KernelVersion::parse(&running.kernel.version);
+    let kernel_ordering = |release: &OS| {
+        KernelVersion::parse(&release.kernel.version)
+            .numbers
+            .cmp(&running_kernel.numbers)
+    };
+
+    let mut certified_flavours: Vec<String> = available_releases
+        .iter()
+        .filter(|release| same_release(running, release))
+        .filter_map(|release| KernelVersion::parse(&release.kernel.version).flavour)
+        .collect();
+    certified_flavours.sort();
+    certified_flavours.dedup();
+
+    let exact_match = available_releases.iter().find(|release| {
+        same_release(running, release) && release.kernel.version == running.kernel.version
+    });
+
+    // Closest first: same release, same flavour, nearest release, newer
+    // kernels before older ones and, finally, nearest kernel. Older kernel
+    // versions are negated so that the biggest one sorts first.
+    let distance = |release: &OS| {
+        let kernel = KernelVersion::parse(&release.kernel.version);
+        let newer = kernel.numbers >= running_kernel.numbers;
+        let kernel_rank: Vec<i64> = kernel
+            .numbers
+            .iter()
+            .map(|&number| {
+                if newer {
+                    number as i64
+                } else {
+                    -(number as i64)
+                }
+            })
+            .collect();
+        (
+            !same_release(running, release),
+            kernel.flavour != running_kernel.flavour,
+            release_number(&release.version).abs_diff(release_number(&running.version)),
+            !newer,
+            kernel_rank,
+        )
+    };
+    let recommended = exact_match
+        .or_else(|| {
+            available_releases
+                .iter()
+                .min_by_key(|release| distance(release))
+        })
+        .cloned();
+
+    ReleaseCompatibility {
+        release_certified: available_releases
+            .iter()
+            .any(|release| same_release(running, release)),
+        kernel_certified: exact_match.is_some(),
+        certified_flavours,
+        newer: available_releases
+            .iter()
+            .filter(|release| kernel_ordering(release) == Ordering::Greater)
+            .cloned()
+            .collect(),
+        older: available_releases
+            .iter()
+            .filter(|release| kernel_ordering(release) == Ordering::Less)
+            .cloned()
+            .collect(),
+        recommended,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::software::KernelPackage;
+
+    fn create_os(version: &str, kernel_version: &str) -> OS {
+        OS {
+            codename: "".to_string(),
+            distributor: "Ubuntu".to_string(),
+            version: version.to_string(),
+            kernel: KernelPackage {
+                name: None,
+                version: kernel_version.to_string(),
+                signature: None,
+                loaded_modules: vec![],
+            },
+        }
+    }
+
+    #[test]
+    fn test_parse_kernel_version() {
+        assert_eq!(
+            KernelVersion::parse("6.8.0-1013-oem"),
+            KernelVersion {
+                numbers: vec![6, 8, 0, 1013],
+                flavour: Some("oem".to_string()),
+            }
+        );
+        assert_eq!(
+            KernelVersion::parse("6.8.0"),
+            KernelVersion {
+                numbers: vec![6, 8, 0],
+                flavour: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_certified_combination() {
+        let running = create_os("24.04", "6.8.0-1009-oem");
+        let available = vec![
+            create_os("22.04", "6.5.0-1027-oem"),
+            create_os("24.04", "6.8.0-1009-oem"),
+        ];
+        let compatibility = check_compatibility(&running, &available);
+        assert!(compatibility.release_certified);
+        assert!(compatibility.kernel_certified);
+        assert_eq!(compatibility.certified_flavours, vec!["oem".to_string()]);
+        assert_eq!(compatibility.older, vec![available[0].clone()]);
+        assert!(compatibility.newer.is_empty());
+        assert_eq!(compatibility.recommended, Some(available[1].clone()));
+    }
+
+    #[test]
+    fn test_recommends_nearest_kernel_of_same_flavour() {
+        let running = create_os("24.04", "6.8.0-1013-oem");
+        let available = vec![
+            create_os("24.04", "6.8.0-1009-oem"),
+            create_os("24.04", "6.8.0-1020-generic"),
+            create_os("24.04", "6.8.0-1030-oem"),
+            create_os("24.04", "6.8.0-1016-oem"),
+        ];
+        let compatibility = check_compatibility(&running, &available);
+        assert!(compatibility.release_certified);
+        assert!(!compatibility.kernel_certified);
+        assert_eq!(
+            compatibility.certified_flavours,
+            vec!["generic".to_string(), "oem".to_string()]
+        );
+        assert_eq!(compatibility.newer.len(), 3);
+        assert_eq!(compatibility.older, vec![available[0].clone()]);
+        assert_eq!(compatibility.recommended, Some(available[3].clone()));
+    }
+
+    #[test]
+    fn test_running_release_not_certified() {
+        let running = create_os("25.10", "6.17.0-5-generic");
+        let available = vec![
+            create_os("22.04", "5.15.0-125-generic"),
+            create_os("24.04", "6.8.0-31-generic"),
+        ];
+        let compatibility = check_compatibility(&running, &available);
+        assert!(!compatibility.release_certified);
+        assert!(!compatibility.kernel_certified);
+        assert!(compatibility.certified_flavours.is_empty());
+        assert_eq!(compatibility.older.len(), 2);
+        assert_eq!(compatibility.recommended, Some(available[1].clone()));
+    }
+
+    #[test]
+    fn test_no_certified_releases() {
+        let running = create_os("24.04", "6.8.0-31-generic");
+        assert_eq!(
+            check_compatibility(&running, &[]),
+            ReleaseCompatibility::default()
+        );
+    }
+}
@@ -16,6 +16,8 @@
  *        Nadzeya Hutsko <nadzeya.hutsko@canonical.com>
  */
 
+use std::time::Duration;
+
 pub const CERT_STATUS_ENDPOINT: &str = "/v1/certification/status";
 
 pub const CPU_MAX_FREQ_FILE_PATH: &str = "/sys/devices/system/cpu/cpu0/cpufreq/cpuinfo_max_freq";
@@ -28,11 +30,34 @@ pub const OS_RELEASE_FILE_PATH: &str = env!("OS_RELEASE_FILE_PATH");
 
 pub const LSMOD: &str = "/usr/bin/lsmod";
 
+// Where snapd writes the system proxy settings
+pub const ETC_ENVIRONMENT_FILE_PATH: &str = "/etc/environment";
+
 pub const CACHE_EXPIRATION_IF_CERTIFIED: u64 = 60 * 60 * 24 * 30; // 30  days
 pub const CACHE_EXPIRATION_IF_NOT_CERTIFIED: u64 = 60 * 60 * 24; // 1 day
+pub const CACHE_EXPIRATION_MAX: u64 = 60 * 60 * 24 * 365; // 1 year
 pub const CACHE_FILE_NAME: &str = "hw_cache.json";
 pub const SETTINGS_FILE_NAME: &str = "hw_settings.json";
+pub const LOCK_FILE_NAME: &str = "hw_cache.lock";
+pub const CACHE_KEY_FILE_NAME: &str = "hw_cache.key";
+pub const CACHE_LOCK_TIMEOUT: u64 = 10; // seconds
+pub const HISTORY_MAX_ENTRIES: usize = 100;
+pub const CACHE_MAX_ENTRIES: usize = 16;
+pub const RETRY_BACKOFF_BASE: u64 = 60; // 1 minute
+pub const RETRY_BACKOFF_MAX: u64 = 60 * 60 * 6; // 6 hours
+pub const CONNECT_TIMEOUT: u64 = 10; // seconds
+pub const REQUEST_TIMEOUT: u64 = 30; // seconds
+pub const CANCELLATION_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// Bump when the fields hashed by CertificationStatusRequest::fingerprint() change
+pub const FINGERPRINT_VERSION: u32 = 2;
+
+pub const MACHINE_ID_PATH: &str = "/etc/machine-id";
+// Mixed into the machine ID to derive the key the redacted fields are hashed with
+pub const REDACTION_KEY_CONTEXT: &str = "hwctl redaction";
 
 pub const SOCKET_NAME: &str = "hwctl.varlink";
+// Clients the daemon serves at once; the others wait to be accepted
+pub const MAX_CONNECTIONS: usize = 16;
 
 pub const DEFAULT_SERVER_URL: &str = "https://hw.ubuntu.com";
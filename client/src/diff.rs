@@ -0,0 +1,326 @@
+/* Copyright 2026 Canonical Ltd.
+ *
+ * This program is free software: you can redistribute it and/or
+ * modify it under the terms of the GNU Lesser General Public License
+ * version 3, as published by the Free Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Lesser General Public License for more details.
+ *
+ * You should have received a copy of the GNU Lesser General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::models::request_validators::CertificationStatusRequest;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    /// An item was added to a list, e.g. a new PCI peripheral or kernel module.
+    Added,
+    /// An item was removed from a list.
+    Removed,
+    /// A single value changed, e.g. the BIOS version.
+    Modified,
+}
+
+/// A single field-level difference between two hardware snapshots.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct HardwareChange {
+    /// Dotted path of the changed field, e.g. `bios.version` or `pci_peripherals`.
+    pub field: String,
+    pub kind: ChangeKind,
+    /// The previous value, if any. List items and objects are rendered as JSON.
+    pub old: Option<String>,
+    /// The new value, if any.
+    pub new: Option<String>,
+}
+
+/// Decides which of the changed fields identify the hardware and which ones
+/// are volatile software state, such as the kernel version or the loaded
+/// modules, that shouldn't be reported as a hardware mismatch.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct ComparisonRules {
+    /// Dotted paths of the software fields. A path also covers every field
+    /// below it, so `os` covers `os.kernel.version`.
+    pub software_fields: Vec<String>,
+}
+
+impl Default for ComparisonRules {
+    fn default() -> Self {
+        ComparisonRules {
+            software_fields: vec!["os".to_string()],
+        }
+    }
+}
+
+impl ComparisonRules {
+    /// Returns true if the given field is software state according to these rules.
+    pub fn is_software(&self, field: &str) -> bool {
+        self.software_fields.iter().any(|software_field| {
+            field == software_field
+                || field
+                    .strip_prefix(software_field.as_str())
+                    .is_some_and(|rest| rest.starts_with('.'))
+        })
+    }
+
+    /// Returns true if any of the changes modifies the hardware identity.
+    pub fn hardware_changed(&self, changes: &[HardwareChange]) -> bool {
+        changes
+            .iter()
+            .any(|change| !self.is_software(&change.field))
+    }
+
+    /// Returns true if any of the changes modifies the software state.
+    pub fn software_changed(&self, changes: &[HardwareChange]) -> bool {
+        changes.iter().any(|change| self.is_software(&change.field))
+    }
+}
+
+impl CertificationStatusRequest {
+    /// Returns the field-level changes needed to go from this snapshot to `newer`.
+    ///
+    /// Lists of devices and kernel modules are compared as sets, so only the
+    /// added and removed items are reported, not their position.
+    pub fn diff(&self, newer: &CertificationStatusRequest) -> Vec<HardwareChange> {
+        let old = serde_json::to_value(self).expect("requests are always serializable");
+        let new = serde_json::to_value(newer).expect("requests are always serializable");
+        let mut changes = Vec::new();
+        diff_values("", &old, &new, &mut changes);
+        changes
+    }
+}
+
+fn render(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::String(string) => Some(string.clone()),
+        other => Some(other.to_string()),
+    }
+}
+
+fn join_path(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", prefix, key)
+    }
+}
+
+/// Whether a list is a collection of items (compared as a set) rather than a
+/// single value, such as the processor identifier bytes.
+fn is_collection(items: &[Value]) -> bool {
+    items
+        .iter()
+        .all(|item| item.is_object() || item.is_string())
+}
+
+fn diff_values(path: &str, old: &Value, new: &Value, changes: &mut Vec<HardwareChange>) {
+    if old == new {
+        return;
+    }
+    match (old, new) {
+        (Value::Object(old_fields), Value::Object(new_fields)) => {
+            for (key, old_value) in old_fields {
+                let new_value = new_fields.get(key).unwrap_or(&Value::Null);
+                diff_values(&join_path(path, key), old_value, new_value, changes);
+            }
+            for (key, new_value) in new_fields {
+                if !old_fields.contains_key(key) {
+                    diff_values(&join_path(path, key), &Value::Null, new_value, changes);
+                }
+            }
+        }
+        (Value::Array(old_items), Value::Array(new_items))
+            if is_collection(old_items) && is_collection(new_items) =>
+        {
+            for item in old_items.iter().filter(|item| !new_items.contains(item)) {
+                changes.push(HardwareChange {
+                    field: path.to_string(),
+                    kind: ChangeKind::Removed,
+                    old: render(item),
+                    new: None,
+                });
+            }
+            for item in new_items.iter().filter(|item| !old_items.contains(item)) {
+                changes.push(HardwareChange {
+                    field: path.to_string(),
+                    kind: ChangeKind::Added,
+                    old: None,
+                    new: render(item),
+                });
+            }
+        }
+        (Value::Object(_), Value::Null) | (Value::Null, Value::Object(_)) => {
+            // A whole section appeared or disappeared, e.g. the chassis data.
+            changes.push(HardwareChange {
+                field: path.to_string(),
+                kind: if new.is_null() {
+                    ChangeKind::Removed
+                } else {
+                    ChangeKind::Added
+                },
+                old: render(old),
+                new: render(new),
+            });
+        }
+        _ => changes.push(HardwareChange {
+            field: path.to_string(),
+            kind: ChangeKind::Modified,
+            old: render(old),
+            new: render(new),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{
+        devices::{Bios, Board, PCIPeripheral, Processor},
+        software::{KernelPackage, OS},
+    };
+
+    fn create_test_hardware_data() -> CertificationStatusRequest {
+        CertificationStatusRequest {
+            architecture: "amd64".to_string(),
+            bios: Some(Bios {
+                firmware_revision: Some("1.7".to_string()),
+                release_date: Some("06/12/2024".to_string()),
+                revision: Some("1.7.0".to_string()),
+                vendor: "Dell Inc.".to_string(),
+                version: "1.7.0".to_string(),
+            }),
+            board: Board {
+                manufacturer: "Dell Inc.".to_string(),
+                product_name: "02395C".to_string(),
+                version: "X04".to_string(),
+            },
+            chassis: None,
+            model: "XPS 13 9340".to_string(),
+            os: OS {
+                codename: "noble".to_string(),
+                distributor: "Ubuntu".to_string(),
+                version: "24.04".to_string(),
+                kernel: KernelPackage {
+                    name: Some("Linux".to_string()),
+                    version: "6.8.0-1013-oem".to_string(),
+                    signature: None,
+                    loaded_modules: vec!["nvme".to_string(), "zfs".to_string()],
+                },
+            },
+            pci_peripherals: vec![PCIPeripheral {
+                pci_id: "8086:7e40".to_string(),
+                name: "Wireless".to_string(),
+                status: None,
+                vendor: "Intel".to_string(),
+            }],
+            processor: Processor {
+                identifier: Some([164, 6, 10, 0, 255, 251, 235, 191]),
+                frequency: 4500,
+                version: "Intel(R) Core(TM) Ultra 7 155H".to_string(),
+                manufacturer: "Intel(R) Corporation".to_string(),
+            },
+            usb_peripherals: vec![],
+            vendor: "Dell Inc.".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_no_changes() {
+        let hardware_data = create_test_hardware_data();
+        assert_eq!(hardware_data.diff(&hardware_data), vec![]);
+    }
+
+    #[test]
+    fn test_bios_update() {
+        let old = create_test_hardware_data();
+        let mut new = old.clone();
+        new.bios.as_mut().unwrap().version = "1.8.0".to_string();
+        assert_eq!(
+            old.diff(&new),
+            vec![HardwareChange {
+                field: "bios.version".to_string(),
+                kind: ChangeKind::Modified,
+                old: Some("1.7.0".to_string()),
+                new: Some("1.8.0".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_kernel_change() {
+        let old = create_test_hardware_data();
+        let mut new = old.clone();
+        new.os.kernel.version = "6.8.0-1020-oem".to_string();
+        new.os.kernel.loaded_modules = vec!["zfs".to_string(), "nvme".to_string()];
+        assert_eq!(
+            old.diff(&new),
+            vec![HardwareChange {
+                field: "os.kernel.version".to_string(),
+                kind: ChangeKind::Modified,
+                old: Some("6.8.0-1013-oem".to_string()),
+                new: Some("6.8.0-1020-oem".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_peripherals_added_and_removed() {
+        let old = create_test_hardware_data();
+        let mut new = old.clone();
+        new.pci_peripherals[0].pci_id = "8086:7e7d".to_string();
+        let changes = old.diff(&new);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].field, "pci_peripherals");
+        assert_eq!(changes[0].kind, ChangeKind::Removed);
+        assert!(changes[0].old.as_ref().unwrap().contains("8086:7e40"));
+        assert_eq!(changes[1].kind, ChangeKind::Added);
+        assert!(changes[1].new.as_ref().unwrap().contains("8086:7e7d"));
+    }
+
+    #[test]
+    fn test_motherboard_swap() {
+        let old = create_test_hardware_data();
+        let mut new = old.clone();
+        new.board.product_name = "0KRNM1".to_string();
+        new.processor.identifier = Some([165, 6, 10, 0, 255, 251, 235, 191]);
+        let fields: Vec<String> = old.diff(&new).into_iter().map(|c| c.field).collect();
+        assert_eq!(fields, vec!["board.product_name", "processor.identifier"]);
+    }
+
+    #[test]
+    fn test_default_rules_separate_software() {
+        let rules = ComparisonRules::default();
+        let old = create_test_hardware_data();
+        let mut new = old.clone();
+        new.os.kernel.version = "6.8.0-1020-oem".to_string();
+        new.os.kernel.loaded_modules.push("kvm".to_string());
+        let changes = old.diff(&new);
+        assert!(!rules.hardware_changed(&changes));
+        assert!(rules.software_changed(&changes));
+
+        new.bios.as_mut().unwrap().version = "1.8.0".to_string();
+        let changes = old.diff(&new);
+        assert!(rules.hardware_changed(&changes));
+        assert!(rules.software_changed(&changes));
+    }
+
+    #[test]
+    fn test_custom_rules() {
+        let rules = ComparisonRules {
+            software_fields: vec!["os.kernel.loaded_modules".to_string(), "bios".to_string()],
+        };
+        assert!(rules.is_software("os.kernel.loaded_modules"));
+        assert!(rules.is_software("bios.version"));
+        assert!(!rules.is_software("os.kernel.version"));
+        assert!(!rules.is_software("biosx"));
+    }
+}
@@ -35,6 +35,41 @@ pub(crate) fn append_to_pathbuf(p: PathBuf, s: &str) -> PathBuf {
     p.into()
 }
 
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub(crate) fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok())
+        .collect()
+}
+
+/// Returns how many seconds a response may be cached according to the value of
+/// its `Cache-Control` header. `no-cache` and `no-store` mean it must not be
+/// reused, so they return zero.
+pub(crate) fn parse_max_age(cache_control: &str) -> Option<u64> {
+    let directives: Vec<String> = cache_control
+        .split(',')
+        .map(|directive| directive.trim().to_ascii_lowercase())
+        .collect();
+    if directives
+        .iter()
+        .any(|directive| directive == "no-cache" || directive == "no-store")
+    {
+        return Some(0);
+    }
+    directives.iter().find_map(|directive| {
+        directive
+            .strip_prefix("max-age=")
+            .and_then(|seconds| seconds.trim_matches('"').parse::<u64>().ok())
+    })
+}
+
 fn join_paths(base_path: &str, relative_path: &str) -> String {
     let mut base_path = base_path.to_string();
     if !base_path.ends_with("/") {
@@ -90,7 +125,12 @@ pub fn get_socket_path(binary_type: BinaryType) -> Result<(String, String), anyh
 #[cfg(test)]
 pub(crate) mod test_utils {
     use crate::collectors::os_info::CommandRunner;
+    use crate::models::request_validators::CertificationStatusRequest;
+    use crate::models::response_validators::CertificationStatusResponse;
+    use crate::transport::{ConnectionSettings, Transport, TransportError, TransportResponse};
     use anyhow::{anyhow, bail, Result};
+    use std::sync::Arc;
+    use std::time::Duration;
     use std::{collections::HashMap, env, fs::read_dir, path::PathBuf};
 
     type SystemCommand<'args> = (&'args str, Vec<&'args str>);
@@ -157,6 +197,82 @@ pub(crate) mod test_utils {
         }
         content
     }
+
+    /// Answers every request with `result`, after waiting `delay`.
+    pub(crate) struct MockTransport {
+        pub(crate) result: Result<TransportResponse, TransportError>,
+        pub(crate) delay: Duration,
+    }
+
+    impl Transport for MockTransport {
+        fn send(
+            &self,
+            _: &str,
+            _: &CertificationStatusRequest,
+            _: &ConnectionSettings,
+        ) -> Result<TransportResponse, TransportError> {
+            std::thread::sleep(self.delay);
+            self.result.clone()
+        }
+    }
+
+    pub(crate) fn answering(response: CertificationStatusResponse) -> Option<Arc<dyn Transport>> {
+        Some(Arc::new(MockTransport {
+            result: Ok(TransportResponse {
+                response: Some(response),
+                max_age: None,
+                etag: None,
+            }),
+            delay: Duration::ZERO,
+        }))
+    }
+
+    pub(crate) fn failing(error: TransportError) -> Option<Arc<dyn Transport>> {
+        Some(Arc::new(MockTransport {
+            result: Err(error),
+            delay: Duration::ZERO,
+        }))
+    }
+
+    pub(crate) fn certified() -> CertificationStatusResponse {
+        CertificationStatusResponse::Certified {
+            certified_url: "https://certification.ubuntu.com/hardware/x86_64".to_string(),
+            architecture: "x86_64".to_string(),
+            available_releases: vec![],
+            bios: Default::default(),
+            board: Default::default(),
+            chassis: Default::default(),
+        }
+    }
+
+    pub(crate) fn certified_image_exists() -> CertificationStatusResponse {
+        CertificationStatusResponse::CertifiedImageExists {
+            certified_url: "https://certification.ubuntu.com/hardware/x86_64".to_string(),
+            architecture: "x86_64".to_string(),
+            available_releases: vec![],
+            bios: Default::default(),
+            board: Default::default(),
+            chassis: Default::default(),
+        }
+    }
+
+    pub(crate) fn related_certified_system_exists() -> CertificationStatusResponse {
+        CertificationStatusResponse::RelatedCertifiedSystemExists {
+            certified_url: "https://certification.ubuntu.com/hardware/x86_64".to_string(),
+            architecture: "x86_64".to_string(),
+            available_releases: vec![],
+            bios: Default::default(),
+            board: Default::default(),
+            chassis: Default::default(),
+            gpu: None,
+            audio: None,
+            video: None,
+            network: None,
+            wireless: None,
+            pci_peripherals: vec![],
+            usb_peripherals: vec![],
+        }
+    }
 }
 
 #[cfg(test)]
@@ -181,6 +297,18 @@ mod tests {
         return (basepath, fullpath);
     }
 
+    #[test]
+    fn test_parse_max_age() {
+        assert_eq!(parse_max_age("max-age=604800"), Some(604800));
+        assert_eq!(
+            parse_max_age("public, Max-Age=60, must-revalidate"),
+            Some(60)
+        );
+        assert_eq!(parse_max_age("no-store"), Some(0));
+        assert_eq!(parse_max_age("public"), None);
+        assert_eq!(parse_max_age("max-age=soon"), None);
+    }
+
     // have to use sealed_test because the tests modify environment variables, which can affect other tests if run in parallel
     #[sealed_test]
     fn test_get_socket_path_in_snap() {
@@ -0,0 +1,232 @@
+/* Copyright 2026 Canonical Ltd.
+ *
+ * This program is free software: you can redistribute it and/or
+ * modify it under the terms of the GNU Lesser General Public License
+ * version 3, as published by the Free Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Lesser General Public License for more details.
+ *
+ * You should have received a copy of the GNU Lesser General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Integrity protection for the cache file.
+//!
+//! The cache is signed with an HMAC-SHA256 whose key is stored in a separate
+//! file readable only by its owner (root, for the daemon). A cache edited by
+//! hand, copied from another machine or written with another key doesn't pass
+//! the check. Whoever can read the key, i.e. root, can still forge it.
+//!
+//! Without the key, e.g. for caches written before they were signed, or read
+//! by users other than root, the cache is read unverified.
+
+use anyhow::{bail, Context, Result};
+use hmac::{Hmac, Mac};
+use serde_json::{Map, Value};
+use sha2::Sha256;
+use std::io::{ErrorKind, Read};
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+use crate::{helpers, storage};
+
+/// Top-level field of a signed file that holds its HMAC.
+pub(crate) const INTEGRITY_KEY: &str = "integrity";
+
+const HMAC_PREFIX: &str = "hmac-sha256:";
+const KEY_LENGTH: usize = 32;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// The key stored at a path.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum StoredKey {
+    Trusted(Vec<u8>),
+    /// There is no key, or it can't be trusted: it is invalid, a symbolic
+    /// link, owned by another user than root or the effective one, or other
+    /// users can read or write it. A new key replaces it on the next save.
+    Missing,
+    /// The key exists but this process isn't allowed to read it, e.g. it
+    /// belongs to root and the cache is read by another user.
+    Unreadable,
+}
+
+/// Returns the key stored at `path`.
+pub(crate) fn load_key(path: &Path) -> StoredKey {
+    match storage::read_protected_file(path, true) {
+        Ok(key) if key.len() == KEY_LENGTH => StoredKey::Trusted(key),
+        Err(error)
+            if error
+                .downcast_ref::<std::io::Error>()
+                .is_some_and(|error| error.kind() == ErrorKind::PermissionDenied) =>
+        {
+            StoredKey::Unreadable
+        }
+        _ => StoredKey::Missing,
+    }
+}
+
+/// Returns the key stored at `path`, replacing it with a new random one if
+/// it can't be trusted. The caller must hold the cache lock.
+///
+/// The key is only created in a folder that only root or the effective user
+/// can modify, since other users could otherwise replace it. A key that
+/// this process can't read is never replaced, so a cache read without it
+/// can't be written back.
+pub(crate) fn load_or_create_key(path: &Path) -> Result<Vec<u8>> {
+    match load_key(path) {
+        StoredKey::Trusted(key) => return Ok(key),
+        StoredKey::Unreadable => bail!(
+            "cannot read the cache integrity key {}, the cache is read-only",
+            path.display()
+        ),
+        StoredKey::Missing => {}
+    }
+    let folder = path
+        .parent()
+        .filter(|folder| !folder.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let metadata =
+        std::fs::metadata(folder).with_context(|| format!("cannot read {}", folder.display()))?;
+    if !storage::is_trusted_owner(metadata.uid()) || metadata.mode() & 0o022 != 0 {
+        bail!(
+            "cannot store the cache integrity key in {}: other users can modify it",
+            folder.display()
+        );
+    }
+    let mut key = vec![0u8; KEY_LENGTH];
+    std::fs::File::open("/dev/urandom")
+        .and_then(|mut random| random.read_exact(&mut key))
+        .context("cannot generate the cache integrity key")?;
+    storage::write_file_atomically(path, &key, 0o600)?;
+    Ok(key)
+}
+
+fn compute_mac(key: &[u8], fields: &Map<String, Value>) -> Result<HmacSha256> {
+    let mut unsigned = fields.clone();
+    unsigned.remove(INTEGRITY_KEY);
+    let mut mac = HmacSha256::new_from_slice(key)?;
+    mac.update(&serde_json::to_vec(&unsigned)?);
+    Ok(mac)
+}
+
+/// Adds the HMAC of `fields` to them.
+pub(crate) fn sign(key: &[u8], fields: &mut Map<String, Value>) -> Result<()> {
+    let tag = compute_mac(key, fields)?.finalize().into_bytes();
+    fields.insert(
+        INTEGRITY_KEY.to_string(),
+        Value::from(format!("{}{}", HMAC_PREFIX, helpers::to_hex(&tag))),
+    );
+    Ok(())
+}
+
+/// Returns true if `fields` were modified since they were signed: their HMAC
+/// is invalid, or missing although the key exists, since it is created
+/// before the first signed save. Without a key, the fields can't be checked
+/// and are read as they are; caches written before they were signed are
+/// then signed on the next save.
+pub(crate) fn is_tampered(key: &StoredKey, fields: &Map<String, Value>) -> bool {
+    match key {
+        StoredKey::Trusted(key) => !verify(key, fields),
+        StoredKey::Missing | StoredKey::Unreadable => false,
+    }
+}
+
+/// Returns true if `fields` hold a valid HMAC of the rest of them.
+pub(crate) fn verify(key: &[u8], fields: &Map<String, Value>) -> bool {
+    let Some(tag) = fields
+        .get(INTEGRITY_KEY)
+        .and_then(|value| value.as_str())
+        .and_then(|value| value.strip_prefix(HMAC_PREFIX))
+        .and_then(helpers::from_hex)
+    else {
+        return false;
+    };
+    compute_mac(key, fields).is_ok_and(|mac| mac.verify_slice(&tag).is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::os::unix::fs::PermissionsExt;
+    use test_temp_dir::test_temp_dir;
+
+    #[test]
+    fn test_sign_and_verify() {
+        let key = vec![7u8; KEY_LENGTH];
+        let mut fields = json!({"status": "NotSeen", "schema_version": 2})
+            .as_object()
+            .unwrap()
+            .clone();
+        sign(&key, &mut fields).unwrap();
+        assert!(verify(&key, &fields));
+        assert!(!verify(&[8u8; KEY_LENGTH], &fields));
+
+        let mut tampered = fields.clone();
+        tampered.insert("status".to_string(), Value::from("Certified"));
+        assert!(!verify(&key, &tampered));
+
+        fields.remove(INTEGRITY_KEY);
+        assert!(!verify(&key, &fields));
+    }
+
+    #[test]
+    fn test_only_bad_signatures_are_tampered() {
+        let key = vec![7u8; KEY_LENGTH];
+        let mut fields = json!({"status": "NotSeen"}).as_object().unwrap().clone();
+        // Unsigned files can't be checked without the key.
+        assert!(!is_tampered(&StoredKey::Missing, &fields));
+        assert!(!is_tampered(&StoredKey::Unreadable, &fields));
+        // But once the key exists, files are signed.
+        assert!(is_tampered(&StoredKey::Trusted(key.clone()), &fields));
+
+        sign(&key, &mut fields).unwrap();
+        assert!(!is_tampered(&StoredKey::Trusted(key), &fields));
+        assert!(is_tampered(
+            &StoredKey::Trusted(vec![8u8; KEY_LENGTH]),
+            &fields
+        ));
+        assert!(!is_tampered(&StoredKey::Unreadable, &fields));
+    }
+
+    #[test]
+    fn test_key_is_private() {
+        let temp_dir = test_temp_dir!();
+        let path = temp_dir.as_path_untracked().join("key");
+        assert_eq!(load_key(&path), StoredKey::Missing);
+
+        let key = load_or_create_key(&path).unwrap();
+        assert_eq!(key.len(), KEY_LENGTH);
+        assert_eq!(
+            std::fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+        assert_eq!(load_key(&path), StoredKey::Trusted(key.clone()));
+
+        // A key that others could read is replaced.
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        assert_eq!(load_key(&path), StoredKey::Missing);
+        let new_key = load_or_create_key(&path).unwrap();
+        assert_ne!(new_key, key);
+        let key = new_key;
+
+        // So is a link to a key, or one owned by another user.
+        let link = temp_dir.as_path_untracked().join("link");
+        std::os::unix::fs::symlink(&path, &link).unwrap();
+        assert_eq!(load_key(&link), StoredKey::Missing);
+        if std::os::unix::fs::chown(&path, Some(65534), None).is_ok() {
+            assert_eq!(load_key(&path), StoredKey::Missing);
+            assert_ne!(load_or_create_key(&path).unwrap(), key);
+        }
+
+        // No key is created where other users could replace it.
+        let shared = temp_dir.as_path_untracked().join("shared");
+        std::fs::create_dir(&shared).unwrap();
+        std::fs::set_permissions(&shared, std::fs::Permissions::from_mode(0o777)).unwrap();
+        assert!(load_or_create_key(&shared.join("key")).is_err());
+    }
+}
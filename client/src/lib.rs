@@ -21,36 +21,114 @@
 #[macro_use]
 extern crate pretty_assertions;
 
+#[cfg(feature = "async")]
+pub mod asynchronous;
+pub mod bundle;
 pub mod cache;
 pub mod collectors;
+pub mod compatibility;
 pub mod constants;
+pub mod diff;
 pub mod helpers;
+mod integrity;
+mod migrations;
 pub mod models;
+mod proxy;
 #[cfg(feature = "pybindings")]
 pub mod py_bindings;
+pub mod redaction;
+mod storage;
+mod tls;
+pub mod transport;
 
 use anyhow::{Error, Result};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::Arc;
 
-use constants::CERT_STATUS_ENDPOINT;
+use constants::{CANCELLATION_POLL_INTERVAL, CERT_STATUS_ENDPOINT};
 use models::{
     request_validators::CertificationStatusRequest,
     response_validators::CertificationStatusResponse, software::OS,
 };
 
-pub use cache::{CertificationStatus, HWCache, StaleStatus};
+pub use bundle::Provenance;
+pub use cache::{CertificationStatus, HWCache, HistoryEntry, StaleStatus};
+pub use compatibility::ReleaseCompatibility;
+pub use diff::{ChangeKind, ComparisonRules, HardwareChange};
+pub use redaction::RedactionPolicy;
 use serde::{Deserialize, Serialize};
+pub use transport::{
+    ConnectionSettings, HttpTransport, Integration, Transport, TransportError, TransportResponse,
+};
 
 #[derive(PartialEq)]
 pub enum CheckCertificationSource {
     Auto,
     Cache,
     Server,
+    /// Like `Auto`, but instead of asking the server it returns the cached
+    /// answer at once, with `refresh_pending` set if `Auto` would have asked.
+    /// The caller then refreshes with `Auto`, e.g. using `spawn_refresh`.
+    Background,
 }
 
+/// Lets a certification check be cancelled from another thread.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels the checks using this token, or any clone of it.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// Returns true if `other` is this token or a clone of it.
+    pub fn same_as(&self, other: &CancellationToken) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// The error returned by a check cancelled through its `CancellationToken`.
+#[derive(Debug)]
+pub struct CheckCancelled;
+
+impl std::fmt::Display for CheckCancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "The certification check was cancelled")
+    }
+}
+
+impl std::error::Error for CheckCancelled {}
+
+/// The error returned when a server other than `DEFAULT_SERVER_URL` is used
+/// while the `allow_custom_url` setting is disabled.
+#[derive(Debug)]
+pub struct CustomUrlNotAllowed;
+
+impl std::fmt::Display for CustomUrlNotAllowed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Custom URL not allowed")
+    }
+}
+
+impl std::error::Error for CustomUrlNotAllowed {}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub enum CertificationSource {
     Cache,
     Server,
+    /// The result was imported from a response bundle, see `bundle`.
+    Imported,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -60,11 +138,29 @@ pub struct PublicCertificationStatus {
     available_releases: Vec<OS>,
     valid_cache: bool,
     hardware_mismatch: bool,
+    #[serde(default)]
+    software_changed: bool,
     stale: bool,
     stale_reason: Option<String>,
+    #[serde(default)]
+    stale_kind: StaleStatus,
     source: CertificationSource,
     remote_access_enabled: bool,
     server_url: String,
+    #[serde(default)]
+    redaction_policy: RedactionPolicy,
+    #[serde(default)]
+    hardware_fingerprint: Option<String>,
+    #[serde(default)]
+    hardware_changes: Vec<HardwareChange>,
+    #[serde(default)]
+    compatibility: ReleaseCompatibility,
+    #[serde(default)]
+    next_retry_at: Option<String>,
+    #[serde(default)]
+    refresh_pending: bool,
+    #[serde(default)]
+    provenance: Option<Provenance>,
 }
 
 impl PublicCertificationStatus {
@@ -80,6 +176,12 @@ impl PublicCertificationStatus {
         return (self.stale, self.stale_reason.clone());
     }
 
+    /// Returns why the reported status is stale, e.g. the kind of failure of
+    /// the last request to the server.
+    pub fn stale_kind(&self) -> StaleStatus {
+        self.stale_kind.clone()
+    }
+
     pub fn extra_data(&self) -> (bool, CertificationSource, bool, String, bool) {
         return (
             self.valid_cache,
@@ -89,6 +191,54 @@ impl PublicCertificationStatus {
             self.hardware_mismatch,
         );
     }
+
+    /// Returns the redaction policy that was applied to the hardware data
+    /// before it was sent to the server and cached.
+    pub fn redaction_policy(&self) -> RedactionPolicy {
+        self.redaction_policy
+    }
+
+    /// Returns the fingerprint of the hardware the reported status belongs to.
+    pub fn hardware_fingerprint(&self) -> Option<String> {
+        self.hardware_fingerprint.clone()
+    }
+
+    /// Returns what changed in the current hardware data since the reported
+    /// status was obtained, including the software fields. Empty unless either
+    /// `hardware_mismatch` or `software_changed` is true.
+    pub fn hardware_changes(&self) -> Vec<HardwareChange> {
+        self.hardware_changes.clone()
+    }
+
+    /// Returns true if the OS or kernel changed since the reported status was
+    /// obtained. Unlike `hardware_mismatch`, this doesn't invalidate the cache.
+    pub fn software_changed(&self) -> bool {
+        self.software_changed
+    }
+
+    /// Returns how the running OS release and kernel compare with the
+    /// certified ones in `available_releases`.
+    pub fn compatibility(&self) -> ReleaseCompatibility {
+        self.compatibility.clone()
+    }
+
+    /// Returns when the server will be asked again in `Auto` mode after a
+    /// failed check, in RFC 3339 format, or None if it isn't backing off.
+    pub fn next_retry_at(&self) -> Option<String> {
+        self.next_retry_at.clone()
+    }
+
+    /// Returns true if this is a cached answer to a `Background` check, and the
+    /// server must be asked to refresh it.
+    pub fn refresh_pending(&self) -> bool {
+        self.refresh_pending
+    }
+
+    /// Returns where the reported status comes from if it was imported from
+    /// a response bundle.
+    pub fn provenance(&self) -> Option<Provenance> {
+        self.provenance.clone()
+    }
 }
 
 fn create_answer(
@@ -98,43 +248,167 @@ fn create_answer(
 ) -> PublicCertificationStatus {
     let (certification_status, certification_certified_url, stale_status, stale_reason) =
         cache.get_status();
+    let available_releases = cache.get_available_releases();
+    let compatibility = compatibility::check_compatibility(&hardware_info.os, &available_releases);
+    let provenance = cache.get_provenance();
+    let source = match source {
+        CertificationSource::Cache if provenance.is_some() => CertificationSource::Imported,
+        source => source,
+    };
     return PublicCertificationStatus {
         status: certification_status,
         certified_url: certification_certified_url,
         valid_cache: !cache.is_expired(),
-        available_releases: cache.get_available_releases(),
+        available_releases,
         hardware_mismatch: !cache.compare_hardware_data(hardware_info),
+        software_changed: cache.software_changed(hardware_info),
         stale: stale_status != StaleStatus::Valid,
         stale_reason,
+        stale_kind: stale_status,
         source,
         remote_access_enabled: cache.get_remote_access_enabled(),
         server_url: cache.get_server_url(),
+        redaction_policy: cache.get_redaction_policy(),
+        hardware_fingerprint: cache.get_hardware_fingerprint(),
+        hardware_changes: cache.diff_hardware_data(hardware_info),
+        compatibility,
+        next_retry_at: cache.get_next_retry_at(),
+        refresh_pending: false,
+        provenance,
     };
 }
 
-#[cfg(not(test))]
-fn send_request(
+/// Returns the certification status, the certified URL and the certified
+/// releases in a server response.
+fn interpret_response(
+    response: &CertificationStatusResponse,
+) -> (CertificationStatus, Option<String>, Vec<OS>) {
+    match response {
+        CertificationStatusResponse::Certified {
+            certified_url,
+            available_releases,
+            ..
+        } => (
+            CertificationStatus::Certified,
+            Some(certified_url.clone()),
+            available_releases.clone(),
+        ),
+        CertificationStatusResponse::CertifiedImageExists {
+            certified_url,
+            available_releases,
+            ..
+        } => (
+            CertificationStatus::CertifiedImageExists,
+            Some(certified_url.clone()),
+            available_releases.clone(),
+        ),
+        CertificationStatusResponse::RelatedCertifiedSystemExists {
+            certified_url,
+            available_releases,
+            ..
+        } => (
+            CertificationStatus::RelatedCertifiedSystemExists,
+            Some(certified_url.clone()),
+            available_releases.clone(),
+        ),
+        _ => (CertificationStatus::NotSeen, None, vec![]),
+    }
+}
+
+/// Sends the request through `transport` in another thread, so that the wait
+/// can be cancelled through `cancel`. Returns None if it was cancelled, in
+/// which case the request is abandoned and ends by itself.
+fn send_request_cancellable(
+    transport: Arc<dyn Transport>,
     server_url: String,
     hardware_info: &CertificationStatusRequest,
-) -> Result<CertificationStatusResponse> {
-    let response = minreq::post(&server_url).with_json(hardware_info)?.send();
-    if response.is_err() {
-        let error = response.err().unwrap();
-        return Result::Err(anyhow::anyhow!("Connecting error: {}", error.to_string()));
-    }
-    let response = response.unwrap().json::<CertificationStatusResponse>();
-    if response.is_err() {
-        let error = response.err().unwrap();
-        return Result::Err(anyhow::anyhow!("Server error: {}", error.to_string()));
-    }
-    return Ok(response.unwrap());
+    connection: ConnectionSettings,
+    cancel: &CancellationToken,
+) -> Option<Result<TransportResponse, TransportError>> {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let hardware_info = hardware_info.clone();
+    std::thread::spawn(move || {
+        let _ = sender.send(transport.send(&server_url, &hardware_info, &connection));
+    });
+    loop {
+        match receiver.recv_timeout(CANCELLATION_POLL_INTERVAL) {
+            Ok(result) => return Some(result),
+            Err(RecvTimeoutError::Timeout) if cancel.is_cancelled() => return None,
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                return Some(Err(TransportError::Connection(
+                    "the request was interrupted".to_string(),
+                )))
+            }
+        }
+    }
 }
 
+/// Returns the certification status of the hardware according to `mode`,
+/// asking the server at `url` over HTTP.
 pub fn check_certification_status(
     url: String,
     mode: CheckCertificationSource,
     hardware_info: &CertificationStatusRequest,
     cache_opt: Option<&mut HWCache>,
+) -> Result<PublicCertificationStatus, Error> {
+    check_certification_status_with_transport(url, mode, hardware_info, cache_opt, None)
+}
+
+/// Like `check_certification_status`, but asks the server through
+/// `transport_opt`, or over HTTP if None.
+pub fn check_certification_status_with_transport(
+    url: String,
+    mode: CheckCertificationSource,
+    hardware_info: &CertificationStatusRequest,
+    cache_opt: Option<&mut HWCache>,
+    transport_opt: Option<Arc<dyn Transport>>,
+) -> Result<PublicCertificationStatus, Error> {
+    check_certification_status_cancellable(
+        url,
+        mode,
+        hardware_info,
+        cache_opt,
+        transport_opt,
+        &CancellationToken::new(),
+    )
+}
+
+/// Like `check_certification_status`, but the request to the server can be
+/// cancelled from another thread through `cancel`, in which case it fails
+/// with `CheckCancelled` and the cache is left as it was.
+pub fn check_certification_status_cancellable(
+    url: String,
+    mode: CheckCertificationSource,
+    hardware_info: &CertificationStatusRequest,
+    cache_opt: Option<&mut HWCache>,
+    transport_opt: Option<Arc<dyn Transport>>,
+    cancel: &CancellationToken,
+) -> Result<PublicCertificationStatus, Error> {
+    check_certification_status_failover(
+        &[url],
+        mode,
+        hardware_info,
+        cache_opt,
+        transport_opt,
+        cancel,
+    )
+}
+
+/// Like `check_certification_status_cancellable`, but asks the servers in
+/// `servers` in order until one of them answers, e.g. a local mirror first and
+/// `DEFAULT_SERVER_URL` as fallback. Servers that failed recently are skipped
+/// until their retry time, except in `Server` mode, where they are asked last.
+///
+/// The results of the server that answered last are reported, and its URL is
+/// in the `server_url` of the answer.
+pub fn check_certification_status_failover(
+    servers: &[String],
+    mode: CheckCertificationSource,
+    hardware_info: &CertificationStatusRequest,
+    cache_opt: Option<&mut HWCache>,
+    transport_opt: Option<Arc<dyn Transport>>,
+    cancel: &CancellationToken,
 ) -> Result<PublicCertificationStatus, Error> {
     let mut local_cache = HWCache::new(None);
     let cache: &mut HWCache = match cache_opt {
@@ -142,10 +416,31 @@ pub fn check_certification_status(
         None => &mut local_cache,
     };
 
-    if (url != constants::DEFAULT_SERVER_URL) && !cache.get_allow_custom_url_enabled() {
-        return Err(anyhow::anyhow!("Custom URL not allowed"));
+    if servers.is_empty() {
+        return Err(anyhow::anyhow!("No server URL given"));
+    }
+    if servers
+        .iter()
+        .any(|url| url != constants::DEFAULT_SERVER_URL)
+        && !cache.get_allow_custom_url_enabled()
+    {
+        return Err(CustomUrlNotAllowed.into());
     }
 
+    // Nothing but the redacted data may be sent to the server or cached.
+    let sent_hardware_info = cache.get_redaction_policy().apply_for_server(hardware_info);
+    let redacted_hardware_info = cache.get_redaction_policy().apply(hardware_info);
+    let hardware_info = &redacted_hardware_info;
+
+    // The servers that haven't failed recently are asked first, in order.
+    let (healthy, failing): (Vec<&String>, Vec<&String>) = servers.iter().partition(|url| {
+        cache.select(url, hardware_info);
+        !cache.is_retry_pending()
+    });
+
+    // Only the results obtained from the given servers are reported.
+    cache.select_latest(servers, hardware_info);
+
     let cache_answer =
         |cache: &HWCache| create_answer(cache, CertificationSource::Cache, hardware_info);
 
@@ -161,7 +456,11 @@ pub fn check_certification_status(
         return Ok(cache_answer(cache));
     }
 
-    if !cache.is_expired() && mode != CheckCertificationSource::Server {
+    // The results for other hardware are only reported in `Cache` mode.
+    if !cache.is_expired()
+        && !cache.is_for_other_hardware()
+        && mode != CheckCertificationSource::Server
+    {
         return Ok(cache_answer(cache));
     }
 
@@ -169,138 +468,125 @@ pub fn check_certification_status(
         return Ok(cache_answer(cache));
     }
 
-    let mut server_url = url.clone();
-    server_url.push_str(CERT_STATUS_ENDPOINT);
-    cache.begin_certification(url.clone(), hardware_info);
-    let response = send_request(server_url, hardware_info);
-    if response.is_err() {
-        let error = response.err().unwrap();
-        cache.end_failed_certification(
-            if error.to_string().starts_with("Connecting error:") {
-                StaleStatus::ConnectingError
-            } else {
-                StaleStatus::ServerError
-            },
-            error.to_string(),
-        );
-        return Ok(cache_answer(cache));
+    // Back off after failed checks, so that unreachable servers aren't hammered.
+    let mut urls = healthy;
+    if mode == CheckCertificationSource::Server {
+        urls.extend(failing);
     }
-    let response = response.unwrap();
-    let certification_status: CertificationStatus;
-    let certification_certified_url: Option<String>;
-    let certification_available_releases: Vec<OS>;
-    match response {
-        CertificationStatusResponse::Certified {
-            certified_url,
-            available_releases,
-            ..
-        } => {
-            certification_status = CertificationStatus::Certified;
-            certification_certified_url = Some(certified_url);
-            certification_available_releases = available_releases;
-        }
-        CertificationStatusResponse::CertifiedImageExists {
-            certified_url,
-            available_releases,
-            ..
-        } => {
-            certification_status = CertificationStatus::CertifiedImageExists;
-            certification_certified_url = Some(certified_url);
-            certification_available_releases = available_releases;
-        }
-        CertificationStatusResponse::RelatedCertifiedSystemExists {
-            certified_url,
-            available_releases,
-            ..
-        } => {
-            certification_status = CertificationStatus::RelatedCertifiedSystemExists;
-            certification_certified_url = Some(certified_url);
-            certification_available_releases = available_releases;
-        }
-        _ => {
-            certification_status = CertificationStatus::NotSeen;
-            certification_certified_url = None;
-            certification_available_releases = vec![];
-        }
+    if urls.is_empty() {
+        return Ok(cache_answer(cache));
     }
 
-    cache.end_success_certification(
-        certification_status,
-        certification_certified_url,
-        certification_available_releases,
-    );
-    return Ok(create_answer(
-        cache,
-        CertificationSource::Server,
-        hardware_info,
-    ));
-}
-
-#[cfg(test)]
-fn send_request(
-    server_url: String,
-    _: &CertificationStatusRequest,
-) -> Result<CertificationStatusResponse> {
-    let only_url = server_url.as_str().split("/").next().unwrap();
-    if only_url.starts_with("certified_") {
-        let arch = only_url.split("_").nth(1).unwrap();
-        return Result::Ok(CertificationStatusResponse::Certified {
-            certified_url: format!("https://certification.ubuntu.com/hardware/{}", arch),
-            architecture: arch.to_string(),
-            available_releases: vec![],
-            bios: Default::default(),
-            board: Default::default(),
-            chassis: Default::default(),
-        });
+    if mode == CheckCertificationSource::Background {
+        let mut answer = cache_answer(cache);
+        answer.refresh_pending = true;
+        return Ok(answer);
     }
 
-    if only_url.starts_with("relatedcertifiedsystemexists_") {
-        let arch = only_url.split("_").nth(1).unwrap();
-        return Result::Ok(CertificationStatusResponse::RelatedCertifiedSystemExists {
-            certified_url: format!("https://certification.ubuntu.com/hardware/{}", arch),
-            architecture: arch.to_string(),
-            available_releases: vec![],
-            bios: Default::default(),
-            board: Default::default(),
-            chassis: Default::default(),
-            gpu: None,
-            audio: None,
-            video: None,
-            network: None,
-            wireless: None,
-            pci_peripherals: vec![],
-            usb_peripherals: vec![],
-        });
+    let transport = transport_opt.unwrap_or_else(|| Arc::new(HttpTransport::default()));
+    for url in urls {
+        if cancel.is_cancelled() {
+            return Err(CheckCancelled.into());
+        }
+        let mut server_url = url.clone();
+        server_url.push_str(CERT_STATUS_ENDPOINT);
+        cache.begin_certification(url.clone(), hardware_info)?;
+        // The cached answer for this hardware is only sent again if it changed.
+        let connection = ConnectionSettings::new(&server_url, cache).revalidating(cache.get_etag());
+        let revalidating = connection.etag().is_some();
+        let Some(response) = send_request_cancellable(
+            transport.clone(),
+            server_url,
+            &sent_hardware_info,
+            connection,
+            cancel,
+        ) else {
+            cache.cancel_certification()?;
+            return Err(CheckCancelled.into());
+        };
+        let TransportResponse {
+            response,
+            max_age,
+            etag,
+        } = match response {
+            Ok(response) => response,
+            Err(error) => {
+                // Recorded for this server only, which is skipped until its
+                // retry time while the next one is asked.
+                cache.end_failed_certification(error.stale_status(), error.to_string())?;
+                continue;
+            }
+        };
+        match response {
+            Some(response) => {
+                let (
+                    certification_status,
+                    certification_certified_url,
+                    certification_available_releases,
+                ) = interpret_response(&response);
+                cache.end_success_certification(
+                    certification_status,
+                    certification_certified_url,
+                    certification_available_releases,
+                    max_age,
+                    etag,
+                )?;
+            }
+            None if revalidating => cache.end_revalidated_certification(max_age, etag)?,
+            None => {
+                cache.end_failed_certification(
+                    StaleStatus::ServerError,
+                    "The server answered Not Modified to a request without ETag".to_string(),
+                )?;
+                continue;
+            }
+        }
+        return Ok(create_answer(
+            cache,
+            CertificationSource::Server,
+            hardware_info,
+        ));
     }
 
-    if only_url.starts_with("certifiedimageexists_") {
-        let arch = only_url.split("_").nth(1).unwrap();
-        return Result::Ok(CertificationStatusResponse::CertifiedImageExists {
-            certified_url: format!("https://certification.ubuntu.com/hardware/{}", arch),
-            architecture: arch.to_string(),
-            available_releases: vec![],
-            bios: Default::default(),
-            board: Default::default(),
-            chassis: Default::default(),
-        });
-    }
+    // None of the servers answered.
+    cache.select_latest(servers, hardware_info);
+    Ok(cache_answer(cache))
+}
 
-    if only_url.starts_with("connectionerror") {
-        return Result::Err(anyhow::anyhow!(
-            "Connecting error: simulated connection error"
-        ));
-    }
-    return Result::Ok(CertificationStatusResponse::NotSeen);
+/// Runs an `Auto` check in a new thread, with the cache in `cache_folder`, or
+/// the default one if None. Used to refresh the cache after a `Background`
+/// check made with the same cache returned `refresh_pending`.
+pub fn spawn_refresh(
+    url: String,
+    hardware_info: CertificationStatusRequest,
+    cache_folder: Option<PathBuf>,
+    transport_opt: Option<Arc<dyn Transport>>,
+) -> std::thread::JoinHandle<Result<PublicCertificationStatus>> {
+    std::thread::spawn(move || {
+        let mut cache = HWCache::new(cache_folder.as_deref());
+        check_certification_status_with_transport(
+            url,
+            CheckCertificationSource::Auto,
+            &hardware_info,
+            Some(&mut cache),
+            transport_opt,
+        )
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::helpers::test_utils::{
+        answering, certified, certified_image_exists, failing, related_certified_system_exists,
+        MockTransport,
+    };
     use crate::models::software::OS;
     use crate::models::{
         devices::{Board, Processor},
         software::KernelPackage,
     };
+    use std::time::Duration;
     use test_temp_dir::{test_temp_dir, TestTempDir};
 
     fn create_test_hardware_data(arch: String) -> CertificationStatusRequest {
@@ -347,40 +633,174 @@ mod tests {
 
     fn new_cache(temp_dir: &std::path::Path) -> HWCache {
         let mut cache = HWCache::new(Some(temp_dir));
-        cache.set_allow_custom_url_enabled(true);
+        cache.set_allow_custom_url_enabled(true).unwrap();
         return cache;
     }
 
+    const SERVER_URL: &str = "https://hw.example.com";
+    const STAGING_URL: &str = "https://staging.hw.example.com";
+    const MIRROR_URL: &str = "https://mirror.hw.example.com";
+
     #[test]
     fn test_connection_error() {
         let temp_dir = create_temporal_cache_folder();
         let mut cache = new_cache(temp_dir.as_path_untracked());
-        cache.set_remote_access_enabled(true);
+        cache.set_remote_access_enabled(true).unwrap();
 
         let hardware_info = create_test_hardware_data("x86_64".to_string());
-        let _ = check_certification_status(
-            "connectionerror_x86_64".to_string(),
+        let _ = check_certification_status_with_transport(
+            SERVER_URL.to_string(),
             CheckCertificationSource::Auto,
             &hardware_info,
             Some(&mut cache),
+            failing(TransportError::Connection("connection refused".to_string())),
         );
         let (_, _, staled, _) = cache.get_status();
         assert!(staled == StaleStatus::ConnectingError);
         keep_temp_dir_alive(&temp_dir);
     }
 
+    #[test]
+    fn test_timeout() {
+        let temp_dir = create_temporal_cache_folder();
+        let mut cache = new_cache(temp_dir.as_path_untracked());
+        cache.set_remote_access_enabled(true).unwrap();
+
+        let hardware_info = create_test_hardware_data("x86_64".to_string());
+        let data = check_certification_status_with_transport(
+            SERVER_URL.to_string(),
+            CheckCertificationSource::Auto,
+            &hardware_info,
+            Some(&mut cache),
+            failing(TransportError::Timeout("timed out".to_string())),
+        )
+        .unwrap();
+        assert!(data.stale);
+        let (_, _, staled, _) = cache.get_status();
+        assert!(staled == StaleStatus::Timeout);
+        assert_eq!(cache.get_failure_count(), 1);
+        keep_temp_dir_alive(&temp_dir);
+    }
+
+    #[test]
+    fn test_server_errors_are_typed() {
+        let temp_dir = create_temporal_cache_folder();
+        let mut cache = new_cache(temp_dir.as_path_untracked());
+        cache.set_remote_access_enabled(true).unwrap();
+        let hardware_info = create_test_hardware_data("x86_64".to_string());
+
+        let data = check_certification_status_with_transport(
+            SERVER_URL.to_string(),
+            CheckCertificationSource::Server,
+            &hardware_info,
+            Some(&mut cache),
+            failing(TransportError::Rejected {
+                status: 422,
+                detail: "body.architecture: Field required".to_string(),
+            }),
+        )
+        .unwrap();
+        assert_eq!(
+            data.stale_kind(),
+            StaleStatus::RequestRejected { status: 422 }
+        );
+        assert_eq!(
+            data.stale_status().1.unwrap(),
+            "The server rejected the request (HTTP 422): body.architecture: Field required"
+        );
+
+        let data = check_certification_status_with_transport(
+            SERVER_URL.to_string(),
+            CheckCertificationSource::Server,
+            &hardware_info,
+            Some(&mut cache),
+            failing(TransportError::RateLimited {
+                retry_after: Some(3600),
+            }),
+        )
+        .unwrap();
+        assert_eq!(
+            data.stale_kind(),
+            StaleStatus::RateLimited {
+                retry_after: Some(3600)
+            }
+        );
+        // The server isn't asked again before the time it asked for.
+        let next_retry_at =
+            chrono::DateTime::parse_from_rfc3339(&data.next_retry_at().unwrap()).unwrap();
+        let delay = next_retry_at.with_timezone(&chrono::Utc) - chrono::Utc::now();
+        assert!(delay > chrono::Duration::seconds(3500));
+        keep_temp_dir_alive(&temp_dir);
+    }
+
+    #[test]
+    fn test_check_can_be_cancelled() {
+        let temp_dir = create_temporal_cache_folder();
+        let mut cache = new_cache(temp_dir.as_path_untracked());
+        cache.set_remote_access_enabled(true).unwrap();
+        let hardware_info = create_test_hardware_data("x86_64".to_string());
+
+        let cancel = CancellationToken::new();
+        let canceller = cancel.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(200));
+            canceller.cancel();
+        });
+        let started = std::time::Instant::now();
+        let result = check_certification_status_cancellable(
+            SERVER_URL.to_string(),
+            CheckCertificationSource::Server,
+            &hardware_info,
+            Some(&mut cache),
+            Some(Arc::new(MockTransport {
+                result: Ok(TransportResponse {
+                    response: Some(CertificationStatusResponse::NotSeen),
+                    max_age: None,
+                    etag: None,
+                }),
+                delay: Duration::from_secs(10),
+            })),
+            &cancel,
+        );
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(result
+            .unwrap_err()
+            .downcast_ref::<CheckCancelled>()
+            .is_some());
+
+        // A cancelled check isn't a failure of the server.
+        let (status, _, staled, _) = cache.get_status();
+        assert_eq!(status, CertificationStatus::Unknown);
+        assert!(staled == StaleStatus::Valid);
+        assert_eq!(cache.get_failure_count(), 0);
+        assert!(cache.get_history().is_empty());
+
+        // A cancelled token cancels the next checks at once.
+        let result = check_certification_status_cancellable(
+            SERVER_URL.to_string(),
+            CheckCertificationSource::Server,
+            &hardware_info,
+            Some(&mut cache),
+            answering(certified()),
+            &cancel,
+        );
+        assert!(result.is_err());
+        keep_temp_dir_alive(&temp_dir);
+    }
+
     #[test]
     fn test_check_certified_image_exists() {
         let temp_dir = create_temporal_cache_folder();
         let mut cache = new_cache(temp_dir.as_path_untracked());
-        cache.set_remote_access_enabled(true);
+        cache.set_remote_access_enabled(true).unwrap();
 
         let hardware_info = create_test_hardware_data("x86_64".to_string());
-        let data = check_certification_status(
-            "certifiedimageexists_x86_64".to_string(),
+        let data = check_certification_status_with_transport(
+            SERVER_URL.to_string(),
             CheckCertificationSource::Auto,
             &hardware_info,
             Some(&mut cache),
+            answering(certified_image_exists()),
         )
         .unwrap();
         assert_eq!(data.status, CertificationStatus::CertifiedImageExists);
@@ -395,14 +815,15 @@ mod tests {
     fn test_check_is_certified() {
         let temp_dir = create_temporal_cache_folder();
         let mut cache = new_cache(temp_dir.as_path_untracked());
-        cache.set_remote_access_enabled(true);
+        cache.set_remote_access_enabled(true).unwrap();
 
         let hardware_info = create_test_hardware_data("x86_64".to_string());
-        let data = check_certification_status(
-            "certified_x86_64".to_string(),
+        let data = check_certification_status_with_transport(
+            SERVER_URL.to_string(),
             CheckCertificationSource::Auto,
             &hardware_info,
             Some(&mut cache),
+            answering(certified()),
         )
         .unwrap();
         assert_eq!(data.status, CertificationStatus::Certified);
@@ -417,14 +838,15 @@ mod tests {
     fn test_check_related_certified() {
         let temp_dir = create_temporal_cache_folder();
         let mut cache = new_cache(temp_dir.as_path_untracked());
-        cache.set_remote_access_enabled(true);
+        cache.set_remote_access_enabled(true).unwrap();
 
         let hardware_info = create_test_hardware_data("x86_64".to_string());
-        let data = check_certification_status(
-            "relatedcertifiedsystemexists_x86_64".to_string(),
+        let data = check_certification_status_with_transport(
+            SERVER_URL.to_string(),
             CheckCertificationSource::Auto,
             &hardware_info,
             Some(&mut cache),
+            answering(related_certified_system_exists()),
         )
         .unwrap();
         assert_eq!(
@@ -442,14 +864,15 @@ mod tests {
     fn test_check_not_seen() {
         let temp_dir = create_temporal_cache_folder();
         let mut cache = new_cache(temp_dir.as_path_untracked());
-        cache.set_remote_access_enabled(true);
+        cache.set_remote_access_enabled(true).unwrap();
 
         let hardware_info = create_test_hardware_data("x86_64".to_string());
-        let data = check_certification_status(
-            "not_seen_x86_64".to_string(),
+        let data = check_certification_status_with_transport(
+            SERVER_URL.to_string(),
             CheckCertificationSource::Auto,
             &hardware_info,
             Some(&mut cache),
+            answering(CertificationStatusResponse::NotSeen),
         )
         .unwrap();
         assert_eq!(data.status, CertificationStatus::NotSeen);
@@ -466,11 +889,12 @@ mod tests {
         let mut cache = new_cache(temp_dir.as_path_untracked());
 
         let hardware_info = create_test_hardware_data("x86_64".to_string());
-        let data = check_certification_status(
-            "certified_x86_64".to_string(),
+        let data = check_certification_status_with_transport(
+            SERVER_URL.to_string(),
             CheckCertificationSource::Auto,
             &hardware_info,
             Some(&mut cache),
+            answering(certified()),
         )
         .unwrap();
         assert_eq!(data.status, CertificationStatus::Unknown);
@@ -482,14 +906,15 @@ mod tests {
     fn test_check_is_certified_and_cached() {
         let temp_dir = create_temporal_cache_folder();
         let mut cache = new_cache(temp_dir.as_path_untracked());
-        cache.set_remote_access_enabled(true);
+        cache.set_remote_access_enabled(true).unwrap();
 
         let hardware_info = create_test_hardware_data("x86_64".to_string());
-        let data = check_certification_status(
-            "certified_x86_64".to_string(),
+        let data = check_certification_status_with_transport(
+            SERVER_URL.to_string(),
             CheckCertificationSource::Auto,
             &hardware_info,
             Some(&mut cache),
+            answering(certified()),
         )
         .unwrap();
         assert_eq!(data.status, CertificationStatus::Certified);
@@ -498,11 +923,12 @@ mod tests {
         assert_eq!(data.valid_cache, true);
         assert_eq!(data.stale, false);
 
-        let data = check_certification_status(
-            "certified_x86_64".to_string(),
+        let data = check_certification_status_with_transport(
+            SERVER_URL.to_string(),
             CheckCertificationSource::Auto,
             &hardware_info,
             Some(&mut cache),
+            answering(certified()),
         )
         .unwrap();
         assert_eq!(data.status, CertificationStatus::Certified);
@@ -512,18 +938,375 @@ mod tests {
         assert_eq!(data.stale, false);
     }
 
+    #[test]
+    fn test_check_honours_server_max_age() {
+        let temp_dir = create_temporal_cache_folder();
+        let mut cache = new_cache(temp_dir.as_path_untracked());
+        cache.set_remote_access_enabled(true).unwrap();
+
+        let hardware_info = create_test_hardware_data("x86_64".to_string());
+        for _ in 0..2 {
+            let data = check_certification_status_with_transport(
+                SERVER_URL.to_string(),
+                CheckCertificationSource::Auto,
+                &hardware_info,
+                Some(&mut cache),
+                // The server asks not to cache its answer.
+                Some(Arc::new(MockTransport {
+                    result: Ok(TransportResponse {
+                        response: Some(certified()),
+                        max_age: Some(0),
+                        etag: None,
+                    }),
+                    delay: Duration::ZERO,
+                })),
+            )
+            .unwrap();
+            assert_eq!(data.status, CertificationStatus::Certified);
+            assert_eq!(data.source, CertificationSource::Server);
+        }
+    }
+
+    #[test]
+    fn test_check_results_are_kept_per_server() {
+        let temp_dir = create_temporal_cache_folder();
+        let mut cache = new_cache(temp_dir.as_path_untracked());
+        cache.set_remote_access_enabled(true).unwrap();
+        cache.set_allow_custom_url_enabled(true).unwrap();
+
+        let hardware_info = create_test_hardware_data("x86_64".to_string());
+        let data = check_certification_status_with_transport(
+            SERVER_URL.to_string(),
+            CheckCertificationSource::Auto,
+            &hardware_info,
+            Some(&mut cache),
+            answering(certified()),
+        )
+        .unwrap();
+        assert_eq!(data.status, CertificationStatus::Certified);
+
+        let data = check_certification_status_with_transport(
+            STAGING_URL.to_string(),
+            CheckCertificationSource::Cache,
+            &hardware_info,
+            Some(&mut cache),
+            answering(CertificationStatusResponse::NotSeen),
+        )
+        .unwrap();
+        assert_eq!(data.status, CertificationStatus::Unknown);
+        assert_eq!(data.server_url, STAGING_URL);
+
+        let data = check_certification_status_with_transport(
+            STAGING_URL.to_string(),
+            CheckCertificationSource::Server,
+            &hardware_info,
+            Some(&mut cache),
+            answering(CertificationStatusResponse::NotSeen),
+        )
+        .unwrap();
+        assert_eq!(data.status, CertificationStatus::NotSeen);
+
+        let data = check_certification_status_with_transport(
+            SERVER_URL.to_string(),
+            CheckCertificationSource::Auto,
+            &hardware_info,
+            Some(&mut cache),
+            answering(certified()),
+        )
+        .unwrap();
+        assert_eq!(data.status, CertificationStatus::Certified);
+        assert_eq!(data.source, CertificationSource::Cache);
+        assert_eq!(data.server_url, SERVER_URL);
+    }
+
+    #[test]
+    fn test_check_backs_off_after_failures() {
+        let temp_dir = create_temporal_cache_folder();
+        let mut cache = new_cache(temp_dir.as_path_untracked());
+        cache.set_remote_access_enabled(true).unwrap();
+
+        let hardware_info = create_test_hardware_data("x86_64".to_string());
+        let check = |cache: &mut HWCache, mode| {
+            check_certification_status_with_transport(
+                SERVER_URL.to_string(),
+                mode,
+                &hardware_info,
+                Some(cache),
+                failing(TransportError::Connection("connection refused".to_string())),
+            )
+            .unwrap()
+        };
+        let data = check(&mut cache, CheckCertificationSource::Auto);
+        assert_eq!(data.stale, true);
+        assert!(data.next_retry_at().is_some());
+        assert_eq!(cache.get_history().len(), 1);
+
+        // The server isn't asked again until the retry time...
+        let data = check(&mut cache, CheckCertificationSource::Auto);
+        assert_eq!(data.next_retry_at(), cache.get_next_retry_at());
+        assert_eq!(cache.get_history().len(), 1);
+
+        // ...unless explicitly requested.
+        check(&mut cache, CheckCertificationSource::Server);
+        assert_eq!(cache.get_history().len(), 2);
+        assert_eq!(cache.get_failure_count(), 2);
+
+        keep_temp_dir_alive(&temp_dir);
+    }
+
+    /// Answers for each server with its response, or fails if it has none.
+    struct ServersTransport(Vec<(&'static str, Option<CertificationStatusResponse>)>);
+
+    impl Transport for ServersTransport {
+        fn send(
+            &self,
+            url: &str,
+            _: &CertificationStatusRequest,
+            _: &ConnectionSettings,
+        ) -> Result<TransportResponse, TransportError> {
+            let (_, response) = self
+                .0
+                .iter()
+                .find(|(server, _)| url.starts_with(server))
+                .unwrap();
+            match response {
+                Some(response) => Ok(TransportResponse {
+                    response: Some(response.clone()),
+                    max_age: None,
+                    etag: None,
+                }),
+                None => Err(TransportError::Connection("connection refused".to_string())),
+            }
+        }
+    }
+
+    #[test]
+    fn test_check_fails_over_to_next_server() {
+        let temp_dir = create_temporal_cache_folder();
+        let mut cache = new_cache(temp_dir.as_path_untracked());
+        cache.set_remote_access_enabled(true).unwrap();
+        // Every answer expires at once.
+        cache.set_cache_expiration(0, 0).unwrap();
+
+        let hardware_info = create_test_hardware_data("x86_64".to_string());
+        let servers = [MIRROR_URL.to_string(), SERVER_URL.to_string()];
+        let check = |cache: &mut HWCache, mode, mirror, server| {
+            check_certification_status_failover(
+                &servers,
+                mode,
+                &hardware_info,
+                Some(cache),
+                Some(Arc::new(ServersTransport(vec![
+                    (MIRROR_URL, mirror),
+                    (SERVER_URL, server),
+                ]))),
+                &CancellationToken::new(),
+            )
+            .unwrap()
+        };
+
+        let data = check(
+            &mut cache,
+            CheckCertificationSource::Auto,
+            None,
+            Some(certified()),
+        );
+        assert_eq!(data.status, CertificationStatus::Certified);
+        assert_eq!(data.source, CertificationSource::Server);
+        assert_eq!(data.server_url, SERVER_URL);
+        assert!(!data.stale);
+        let history = cache.get_history();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].server, MIRROR_URL);
+        assert_eq!(history[0].stale, StaleStatus::ConnectingError);
+        assert_eq!(history[1].server, SERVER_URL);
+
+        // The failed mirror isn't asked again until its retry time...
+        let data = check(
+            &mut cache,
+            CheckCertificationSource::Auto,
+            Some(CertificationStatusResponse::NotSeen),
+            Some(certified()),
+        );
+        assert_eq!(data.server_url, SERVER_URL);
+        assert_eq!(cache.get_history().len(), 3);
+
+        // ...unless the server is explicitly requested, and then it is asked
+        // after the healthy ones.
+        let data = check(
+            &mut cache,
+            CheckCertificationSource::Server,
+            Some(CertificationStatusResponse::NotSeen),
+            None,
+        );
+        assert_eq!(data.status, CertificationStatus::NotSeen);
+        assert_eq!(data.server_url, MIRROR_URL);
+        let history = cache.get_history();
+        assert_eq!(history.len(), 5);
+        assert_eq!(history[3].server, SERVER_URL);
+        assert_eq!(history[4].server, MIRROR_URL);
+
+        // The cache reports the server that answered last.
+        let data = check(&mut cache, CheckCertificationSource::Cache, None, None);
+        assert_eq!(data.status, CertificationStatus::NotSeen);
+        assert_eq!(data.server_url, MIRROR_URL);
+        keep_temp_dir_alive(&temp_dir);
+    }
+
+    #[test]
+    fn test_check_fails_when_no_server_answers() {
+        let temp_dir = create_temporal_cache_folder();
+        let mut cache = new_cache(temp_dir.as_path_untracked());
+        cache.set_remote_access_enabled(true).unwrap();
+
+        let hardware_info = create_test_hardware_data("x86_64".to_string());
+        let data = check_certification_status_failover(
+            &[MIRROR_URL.to_string(), SERVER_URL.to_string()],
+            CheckCertificationSource::Server,
+            &hardware_info,
+            Some(&mut cache),
+            failing(TransportError::Timeout("timed out".to_string())),
+            &CancellationToken::new(),
+        )
+        .unwrap();
+        // The failure of the preferred server is reported.
+        assert!(data.stale);
+        assert_eq!(data.stale_kind(), StaleStatus::Timeout);
+        assert_eq!(data.server_url, MIRROR_URL);
+        assert_eq!(cache.get_history().len(), 2);
+        keep_temp_dir_alive(&temp_dir);
+    }
+
+    /// Answers `304 Not Modified` to requests with the ETag of its answer,
+    /// recording the ETags it received.
+    struct RevalidatingTransport(std::sync::Mutex<Vec<Option<String>>>);
+
+    impl Transport for RevalidatingTransport {
+        fn send(
+            &self,
+            _: &str,
+            _: &CertificationStatusRequest,
+            connection: &ConnectionSettings,
+        ) -> Result<TransportResponse, TransportError> {
+            let etag = connection.etag().map(str::to_string);
+            self.0.lock().unwrap().push(etag.clone());
+            Ok(TransportResponse {
+                response: match etag.as_deref() {
+                    Some("\"v1\"") => None,
+                    _ => Some(certified()),
+                },
+                max_age: None,
+                etag: Some("\"v1\"".to_string()),
+            })
+        }
+    }
+
+    #[test]
+    fn test_check_revalidates_cached_answer() {
+        let temp_dir = create_temporal_cache_folder();
+        let mut cache = new_cache(temp_dir.as_path_untracked());
+        cache.set_remote_access_enabled(true).unwrap();
+        let transport = Arc::new(RevalidatingTransport(Default::default()));
+
+        let hardware_info = create_test_hardware_data("x86_64".to_string());
+        let mut check = |hardware_info| {
+            check_certification_status_with_transport(
+                SERVER_URL.to_string(),
+                CheckCertificationSource::Server,
+                hardware_info,
+                Some(&mut cache),
+                Some(transport.clone()),
+            )
+            .unwrap()
+        };
+        let data = check(&hardware_info);
+        assert_eq!(data.status, CertificationStatus::Certified);
+
+        // The unchanged hardware gets a cheap answer that keeps the status.
+        let data = check(&hardware_info);
+        assert_eq!(data.status, CertificationStatus::Certified);
+        assert_eq!(data.source, CertificationSource::Server);
+        assert!(!data.stale);
+
+        // Other hardware is never revalidated with the ETag of this one.
+        check(&create_test_hardware_data("aarch64".to_string()));
+        assert_eq!(
+            *transport.0.lock().unwrap(),
+            vec![None, Some("\"v1\"".to_string()), None]
+        );
+        assert_eq!(cache.get_history().len(), 3);
+        keep_temp_dir_alive(&temp_dir);
+    }
+
+    #[test]
+    fn test_check_in_background_mode() {
+        let temp_dir = create_temporal_cache_folder();
+        let mut cache = new_cache(temp_dir.as_path_untracked());
+        cache.set_remote_access_enabled(true).unwrap();
+        // Every answer expires at once.
+        cache.set_cache_expiration(0, 0).unwrap();
+
+        let hardware_info = create_test_hardware_data("x86_64".to_string());
+        let check = |cache: &mut HWCache, mode| {
+            check_certification_status_with_transport(
+                SERVER_URL.to_string(),
+                mode,
+                &hardware_info,
+                Some(cache),
+                answering(certified()),
+            )
+            .unwrap()
+        };
+        let data = check(&mut cache, CheckCertificationSource::Background);
+        assert_eq!(data.status, CertificationStatus::Unknown);
+        assert_eq!(data.source, CertificationSource::Cache);
+        assert_eq!(data.refresh_pending(), true);
+
+        // The refresh updates the cache the check was made with.
+        let data = spawn_refresh(
+            SERVER_URL.to_string(),
+            hardware_info.clone(),
+            Some(temp_dir.as_path_untracked().to_path_buf()),
+            answering(certified()),
+        )
+        .join()
+        .unwrap()
+        .unwrap();
+        assert_eq!(data.status, CertificationStatus::Certified);
+        assert_eq!(data.source, CertificationSource::Server);
+        assert_eq!(data.refresh_pending(), false);
+
+        let mut cache = new_cache(temp_dir.as_path_untracked());
+        let data = check(&mut cache, CheckCertificationSource::Background);
+        assert_eq!(data.status, CertificationStatus::Certified);
+        assert_eq!(data.source, CertificationSource::Cache);
+        assert_eq!(data.valid_cache, false);
+        assert_eq!(data.refresh_pending(), true);
+        assert_eq!(cache.get_history().len(), 1);
+
+        // Nothing to refresh while the cache is valid.
+        cache.set_cache_expiration(3600, 3600).unwrap();
+        check(&mut cache, CheckCertificationSource::Server);
+        let data = check(&mut cache, CheckCertificationSource::Background);
+        assert_eq!(data.valid_cache, true);
+        assert_eq!(data.refresh_pending(), false);
+
+        keep_temp_dir_alive(&temp_dir);
+    }
+
     #[test]
     fn test_check_is_certified_forced() {
         let temp_dir = create_temporal_cache_folder();
         let mut cache = new_cache(temp_dir.as_path_untracked());
-        cache.set_remote_access_enabled(true);
+        cache.set_remote_access_enabled(true).unwrap();
 
         let hardware_info = create_test_hardware_data("x86_64".to_string());
-        let data = check_certification_status(
-            "certified_x86_64".to_string(),
+        let data = check_certification_status_with_transport(
+            SERVER_URL.to_string(),
             CheckCertificationSource::Auto,
             &hardware_info,
             Some(&mut cache),
+            answering(certified()),
         )
         .unwrap();
         assert_eq!(data.status, CertificationStatus::Certified);
@@ -532,11 +1315,12 @@ mod tests {
         assert_eq!(data.valid_cache, true);
         assert_eq!(data.stale, false);
 
-        let data = check_certification_status(
-            "certified_x86_64".to_string(),
+        let data = check_certification_status_with_transport(
+            SERVER_URL.to_string(),
             CheckCertificationSource::Server,
             &hardware_info,
             Some(&mut cache),
+            answering(certified()),
         )
         .unwrap();
         assert_eq!(data.status, CertificationStatus::Certified);
@@ -552,14 +1336,15 @@ mod tests {
     fn test_check_is_certified_hardware_mismatch() {
         let temp_dir = create_temporal_cache_folder();
         let mut cache = new_cache(temp_dir.as_path_untracked());
-        cache.set_remote_access_enabled(true);
+        cache.set_remote_access_enabled(true).unwrap();
 
         let hardware_info = create_test_hardware_data("x86_64".to_string());
-        let data = check_certification_status(
-            "certified_x86_64".to_string(),
+        let data = check_certification_status_with_transport(
+            SERVER_URL.to_string(),
             CheckCertificationSource::Auto,
             &hardware_info,
             Some(&mut cache),
+            answering(certified()),
         )
         .unwrap();
         assert_eq!(data.status, CertificationStatus::Certified);
@@ -568,12 +1353,14 @@ mod tests {
         assert_eq!(data.valid_cache, true);
         assert_eq!(data.stale, false);
 
+        // The result for the previous hardware is reported.
         let hardware_info = create_test_hardware_data("arm64".to_string());
-        let data = check_certification_status(
-            "certified_arm64".to_string(),
-            CheckCertificationSource::Auto,
+        let data = check_certification_status_with_transport(
+            SERVER_URL.to_string(),
+            CheckCertificationSource::Cache,
             &hardware_info,
             Some(&mut cache),
+            answering(certified()),
         )
         .unwrap();
         assert_eq!(data.status, CertificationStatus::Certified);
@@ -581,13 +1368,37 @@ mod tests {
         assert_eq!(data.hardware_mismatch, true);
         assert_eq!(data.valid_cache, true);
         assert_eq!(data.stale, false);
+        assert_eq!(
+            data.hardware_changes(),
+            vec![HardwareChange {
+                field: "architecture".to_string(),
+                kind: ChangeKind::Modified,
+                old: Some("x86_64".to_string()),
+                new: Some("arm64".to_string()),
+            }]
+        );
 
-        let hardware_info = create_test_hardware_data("arm64".to_string());
-        let data = check_certification_status(
-            "certified_arm64".to_string(),
+        // But it doesn't answer for this hardware in `Auto` mode.
+        let data = check_certification_status_with_transport(
+            SERVER_URL.to_string(),
+            CheckCertificationSource::Auto,
+            &hardware_info,
+            Some(&mut cache),
+            answering(CertificationStatusResponse::NotSeen),
+        )
+        .unwrap();
+        assert_eq!(data.status, CertificationStatus::NotSeen);
+        assert_eq!(data.source, CertificationSource::Server);
+        assert_eq!(data.hardware_mismatch, false);
+        assert_eq!(data.valid_cache, true);
+        assert_eq!(data.stale, false);
+
+        let data = check_certification_status_with_transport(
+            SERVER_URL.to_string(),
             CheckCertificationSource::Server,
             &hardware_info,
             Some(&mut cache),
+            answering(certified()),
         )
         .unwrap();
         assert_eq!(data.status, CertificationStatus::Certified);
@@ -599,18 +1410,112 @@ mod tests {
         keep_temp_dir_alive(&temp_dir);
     }
 
+    #[test]
+    fn test_check_kernel_upgrade_is_not_hardware_mismatch() {
+        let temp_dir = create_temporal_cache_folder();
+        let mut cache = new_cache(temp_dir.as_path_untracked());
+        cache.set_remote_access_enabled(true).unwrap();
+
+        let hardware_info = create_test_hardware_data("x86_64".to_string());
+        check_certification_status_with_transport(
+            SERVER_URL.to_string(),
+            CheckCertificationSource::Auto,
+            &hardware_info,
+            Some(&mut cache),
+            answering(certified()),
+        )
+        .unwrap();
+
+        let mut upgraded_info = hardware_info.clone();
+        upgraded_info.os.kernel.version = "6.8.0-1020-oem".to_string();
+        upgraded_info.os.kernel.loaded_modules = vec!["kvm".to_string()];
+        let data = check_certification_status_with_transport(
+            SERVER_URL.to_string(),
+            CheckCertificationSource::Auto,
+            &upgraded_info,
+            Some(&mut cache),
+            answering(certified()),
+        )
+        .unwrap();
+        assert_eq!(data.status, CertificationStatus::Certified);
+        assert_eq!(data.source, CertificationSource::Cache);
+        assert_eq!(data.hardware_mismatch, false);
+        assert_eq!(data.software_changed(), true);
+        assert_eq!(data.hardware_changes().len(), 2);
+
+        cache
+            .set_comparison_rules(ComparisonRules {
+                software_fields: vec![],
+            })
+            .unwrap();
+        let data = check_certification_status_with_transport(
+            SERVER_URL.to_string(),
+            CheckCertificationSource::Cache,
+            &upgraded_info,
+            Some(&mut cache),
+            answering(certified()),
+        )
+        .unwrap();
+        assert_eq!(data.hardware_mismatch, true);
+        assert_eq!(data.software_changed(), false);
+
+        keep_temp_dir_alive(&temp_dir);
+    }
+
+    #[test]
+    fn test_check_applies_redaction_policy() {
+        let temp_dir = create_temporal_cache_folder();
+        let mut cache = new_cache(temp_dir.as_path_untracked());
+        cache.set_remote_access_enabled(true).unwrap();
+        cache
+            .set_redaction_policy(RedactionPolicy::Minimal)
+            .unwrap();
+
+        let hardware_info = create_test_hardware_data("x86_64".to_string());
+        let data = check_certification_status_with_transport(
+            SERVER_URL.to_string(),
+            CheckCertificationSource::Auto,
+            &hardware_info,
+            Some(&mut cache),
+            answering(certified()),
+        )
+        .unwrap();
+        assert_eq!(data.status, CertificationStatus::Certified);
+        assert_eq!(data.redaction_policy(), RedactionPolicy::Minimal);
+        assert_eq!(
+            data.hardware_fingerprint(),
+            Some(RedactionPolicy::Minimal.apply(&hardware_info).fingerprint())
+        );
+        assert!(!cache.compare_hardware_data(&hardware_info));
+        assert!(cache.compare_hardware_data(&RedactionPolicy::Minimal.apply(&hardware_info)));
+
+        let data = check_certification_status_with_transport(
+            SERVER_URL.to_string(),
+            CheckCertificationSource::Auto,
+            &hardware_info,
+            Some(&mut cache),
+            answering(certified()),
+        )
+        .unwrap();
+        assert_eq!(data.source, CertificationSource::Cache);
+        assert_eq!(data.hardware_mismatch, false);
+
+        keep_temp_dir_alive(&temp_dir);
+    }
+
     #[test]
     fn test_check_is_certified_and_cached_in_cache_mode() {
         let temp_dir = create_temporal_cache_folder();
         let mut cache = new_cache(temp_dir.as_path_untracked());
-        cache.set_remote_access_enabled(true);
+        cache.set_remote_access_enabled(true).unwrap();
 
         let hardware_info = create_test_hardware_data("x86_64".to_string());
-        let data = check_certification_status(
-            "certified_x86_64".to_string(),
+        let data = check_certification_status_with_transport(
+            SERVER_URL.to_string(),
             CheckCertificationSource::Auto,
             &hardware_info,
             Some(&mut cache),
+            answering(certified()),
         )
         .unwrap();
         assert_eq!(data.status, CertificationStatus::Certified);
@@ -620,11 +1525,12 @@ mod tests {
         assert_eq!(data.stale, false);
 
         let hardware_info = create_test_hardware_data("arm64".to_string());
-        let data = check_certification_status(
-            "certified_arm64".to_string(),
+        let data = check_certification_status_with_transport(
+            SERVER_URL.to_string(),
             CheckCertificationSource::Cache,
             &hardware_info,
             Some(&mut cache),
+            answering(certified()),
         )
         .unwrap();
         assert_eq!(data.status, CertificationStatus::Certified);
@@ -0,0 +1,417 @@
+/* Copyright 2026 Canonical Ltd.
+ *
+ * This program is free software: you can redistribute it and/or
+ * modify it under the terms of the GNU Lesser General Public License
+ * version 3, as published by the Free Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Lesser General Public License for more details.
+ *
+ * You should have received a copy of the GNU Lesser General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use crate::models::request_validators::CertificationStatusRequest;
+use anyhow::{anyhow, Result};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{Map, Value};
+use std::path::{Path, PathBuf};
+
+const SCHEMA_VERSION_KEY: &str = "schema_version";
+
+/// Upgrades the fields of a file from one schema version to the next one.
+pub(crate) type Migration = fn(&mut Map<String, Value>);
+
+/// Migrations of `hw_cache.json`. The one at index N upgrades version N to
+/// N + 1, so the current version is the number of migrations. Files written
+/// before the schema was versioned are version 0.
+pub(crate) const CACHE_MIGRATIONS: &[Migration] = &[unversioned_to_v1, cache_entries_per_server];
+
+/// Migrations of `hw_settings.json`, numbered like `CACHE_MIGRATIONS`.
+pub(crate) const SETTINGS_MIGRATIONS: &[Migration] = &[unversioned_to_v1];
+
+/// The first versioned format only adds `schema_version`.
+fn unversioned_to_v1(_fields: &mut Map<String, Value>) {}
+
+/// Fields of the single result kept by version 1 of the cache.
+const CACHE_V1_ENTRY_FIELDS: &[&str] = &[
+    "certification_status",
+    "certification_certified_url",
+    "stale",
+    "stale_reason",
+    "last_attempt_at",
+    "checked_at",
+    "expires_at",
+    "hardware_data",
+    "hardware_fingerprint",
+    "server",
+    "available_releases",
+];
+
+/// Version 2 keeps one result per server and hardware fingerprint in
+/// `entries`, instead of a single result at the top level.
+fn cache_entries_per_server(fields: &mut Map<String, Value>) {
+    let mut entry = Map::new();
+    for field in CACHE_V1_ENTRY_FIELDS {
+        if let Some(value) = fields.remove(*field) {
+            entry.insert(field.to_string(), value);
+        }
+    }
+    let fingerprint = entry
+        .get("hardware_data")
+        .and_then(|data| serde_json::from_value::<CertificationStatusRequest>(data.clone()).ok())
+        .map(|data| data.fingerprint());
+    if !entry.contains_key("hardware_fingerprint") || entry["hardware_fingerprint"].is_null() {
+        entry.insert("hardware_fingerprint".to_string(), Value::from(fingerprint));
+    }
+    let has_result = entry.get("server").is_some_and(|server| server != "")
+        || entry
+            .get("hardware_data")
+            .is_some_and(|data| !data.is_null());
+    let entries = if has_result {
+        vec![Value::Object(entry)]
+    } else {
+        vec![]
+    };
+    fields.insert("entries".to_string(), Value::Array(entries));
+}
+
+/// The fields of a file written by a newer version that this one doesn't
+/// know, together with its schema version, so that they can be written back.
+pub(crate) type NewerFields = Map<String, Value>;
+
+/// The contents of a versioned file.
+pub(crate) enum VersionedFile<T> {
+    /// The file doesn't exist or can't be read.
+    Missing,
+    /// The file was parsed after migrating it to the current version. If it
+    /// is from a newer version, also holds the fields that weren't parsed.
+    Read(T, NewerFields),
+    /// The file couldn't be parsed and was moved aside. Holds its contents if
+    /// they are a JSON object, so that what's valid can be kept.
+    Quarantined(Option<Map<String, Value>>),
+    /// The file failed its integrity check and was moved aside.
+    Untrusted,
+}
+
+/// Checks the integrity of the fields of a file, as stored.
+pub(crate) type Verifier<'a> = &'a dyn Fn(&Map<String, Value>) -> bool;
+
+/// Brings the fields of a file up to the version after the last migration.
+///
+/// Files from a newer version are left untouched: their fields are a
+/// superset of the known ones, which are still read.
+pub(crate) fn migrate(fields: &mut Map<String, Value>, migrations: &[Migration]) -> Result<()> {
+    let version = schema_version(fields)?;
+    for (step, migration) in migrations.iter().enumerate().skip(version) {
+        migration(fields);
+        fields.insert(SCHEMA_VERSION_KEY.to_string(), Value::from(step + 1));
+    }
+    Ok(())
+}
+
+fn schema_version(fields: &Map<String, Value>) -> Result<usize> {
+    match fields.get(SCHEMA_VERSION_KEY) {
+        None => Ok(0),
+        Some(version) => Ok(version
+            .as_u64()
+            .ok_or_else(|| anyhow!("invalid {}: {}", SCHEMA_VERSION_KEY, version))?
+            as usize),
+    }
+}
+
+/// Returns the fields of `data` tagged with the current schema version, or
+/// with the fields of a newer version it was read from, so that downgrading
+/// and upgrading again neither loses them nor migrates the file twice. Unknown
+/// fields nested in known ones are still lost.
+pub(crate) fn to_versioned_fields<T: Serialize>(
+    data: &T,
+    migrations: &[Migration],
+    newer_fields: &NewerFields,
+) -> Result<Map<String, Value>> {
+    let Value::Object(mut fields) = serde_json::to_value(data)? else {
+        return Err(anyhow!("only objects can be versioned"));
+    };
+    fields.insert(
+        SCHEMA_VERSION_KEY.to_string(),
+        Value::from(migrations.len()),
+    );
+    fields.extend(newer_fields.clone());
+    Ok(fields)
+}
+
+/// Serializes `data` like `to_versioned_fields`.
+pub(crate) fn to_versioned_json<T: Serialize>(
+    data: &T,
+    migrations: &[Migration],
+    newer_fields: &NewerFields,
+) -> Result<Vec<u8>> {
+    let fields = to_versioned_fields(data, migrations, newer_fields)?;
+    Ok(serde_json::to_vec_pretty(&fields)?)
+}
+
+/// Returns the fields of a file from a newer version that `data`, parsed from
+/// them, doesn't hold, or nothing if the file isn't newer.
+fn newer_fields<T: Serialize>(
+    fields: &Map<String, Value>,
+    data: &T,
+    migrations: &[Migration],
+) -> Option<NewerFields> {
+    if schema_version(fields).ok()? <= migrations.len() {
+        return Some(NewerFields::new());
+    }
+    let Value::Object(known) = serde_json::to_value(data).ok()? else {
+        return None;
+    };
+    Some(
+        fields
+            .iter()
+            .filter(|(name, _)| !known.contains_key(*name))
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect(),
+    )
+}
+
+/// Reads the file at `path` and migrates it to the current version.
+///
+/// A file that can't be parsed is renamed to `<name>.corrupt-<timestamp>`
+/// instead of being silently overwritten by the next save. If `verify` is
+/// given, a file that fails it is renamed to `<name>.untrusted-<timestamp>`.
+pub(crate) fn read_versioned_file<T: DeserializeOwned + Serialize>(
+    path: &Path,
+    migrations: &[Migration],
+    verify: Option<Verifier>,
+) -> VersionedFile<T> {
+    let Ok(contents) = std::fs::read(path) else {
+        return VersionedFile::Missing;
+    };
+    let mut fields =
+        serde_json::from_slice::<Value>(&contents)
+            .ok()
+            .and_then(|value| match value {
+                Value::Object(fields) => Some(fields),
+                _ => None,
+            });
+    if let (Some(fields), Some(verify)) = (fields.as_ref(), verify) {
+        if !verify(fields) {
+            quarantine(path, "untrusted");
+            return VersionedFile::Untrusted;
+        }
+    }
+    let data = fields.as_mut().and_then(|fields| {
+        migrate(fields, migrations).ok()?;
+        let data = serde_json::from_value::<T>(Value::Object(fields.clone())).ok()?;
+        let newer_fields = newer_fields(fields, &data, migrations)?;
+        Some((data, newer_fields))
+    });
+    match data {
+        Some((data, newer_fields)) => VersionedFile::Read(data, newer_fields),
+        None => {
+            quarantine(path, "corrupt");
+            VersionedFile::Quarantined(fields)
+        }
+    }
+}
+
+fn quarantine(path: &Path, reason: &str) {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(format!(
+        ".{}-{}",
+        reason,
+        chrono::Utc::now().format("%Y%m%dT%H%M%SZ")
+    ));
+    // If the file can't be moved, it is overwritten by the next save as before.
+    let _ = std::fs::rename(path, PathBuf::from(name));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use serde_json::json;
+    use test_temp_dir::test_temp_dir;
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq)]
+    struct Data {
+        enabled: bool,
+    }
+
+    fn add_flag_to_v2(fields: &mut Map<String, Value>) {
+        fields.insert("flag".to_string(), Value::from(true));
+    }
+
+    const TEST_MIGRATIONS: &[Migration] = &[unversioned_to_v1, add_flag_to_v2];
+
+    #[test]
+    fn test_migrate_unversioned() {
+        let mut fields = json!({"enabled": true}).as_object().unwrap().clone();
+        migrate(&mut fields, TEST_MIGRATIONS).unwrap();
+        assert_eq!(
+            Value::Object(fields),
+            json!({"enabled": true, "flag": true, "schema_version": 2})
+        );
+    }
+
+    #[test]
+    fn test_migrate_skips_applied_steps() {
+        let mut fields = json!({"schema_version": 2, "flag": false})
+            .as_object()
+            .unwrap()
+            .clone();
+        migrate(&mut fields, TEST_MIGRATIONS).unwrap();
+        assert_eq!(fields["flag"], Value::from(false));
+
+        let mut newer = json!({"schema_version": 7}).as_object().unwrap().clone();
+        migrate(&mut newer, TEST_MIGRATIONS).unwrap();
+        assert_eq!(newer["schema_version"], Value::from(7));
+
+        let mut invalid = json!({"schema_version": "one"})
+            .as_object()
+            .unwrap()
+            .clone();
+        assert!(migrate(&mut invalid, TEST_MIGRATIONS).is_err());
+    }
+
+    #[test]
+    fn test_cache_v1_result_becomes_an_entry() {
+        let mut fields = json!({
+            "schema_version": 1,
+            "certification_status": "Certified",
+            "server": "https://hw.ubuntu.com",
+            "history": [],
+        })
+        .as_object()
+        .unwrap()
+        .clone();
+        migrate(&mut fields, CACHE_MIGRATIONS).unwrap();
+        assert_eq!(
+            Value::Object(fields),
+            json!({
+                "schema_version": 2,
+                "history": [],
+                "entries": [{
+                    "certification_status": "Certified",
+                    "server": "https://hw.ubuntu.com",
+                    "hardware_fingerprint": null,
+                }],
+            })
+        );
+
+        let mut empty = json!({"schema_version": 1, "server": ""})
+            .as_object()
+            .unwrap()
+            .clone();
+        migrate(&mut empty, CACHE_MIGRATIONS).unwrap();
+        assert_eq!(empty["entries"], json!([]));
+    }
+
+    #[test]
+    fn test_read_versioned_file() {
+        let temp_dir = test_temp_dir!();
+        let path = temp_dir.as_path_untracked().join("data.json");
+
+        assert!(matches!(
+            read_versioned_file::<Data>(&path, TEST_MIGRATIONS, None),
+            VersionedFile::Missing
+        ));
+
+        let contents = to_versioned_json(
+            &Data { enabled: true },
+            TEST_MIGRATIONS,
+            &NewerFields::new(),
+        )
+        .unwrap();
+        std::fs::write(&path, contents).unwrap();
+        match read_versioned_file::<Data>(&path, TEST_MIGRATIONS, None) {
+            VersionedFile::Read(data, newer_fields) => {
+                assert_eq!(data, Data { enabled: true });
+                assert!(newer_fields.is_empty());
+            }
+            _ => panic!("the file should be read"),
+        }
+    }
+
+    #[test]
+    fn test_newer_file_keeps_its_version_and_fields() {
+        let temp_dir = test_temp_dir!();
+        let path = temp_dir.as_path_untracked().join("data.json");
+        let newer = json!({"schema_version": 7, "enabled": true, "color": "red"});
+        std::fs::write(&path, newer.to_string()).unwrap();
+
+        let VersionedFile::Read(mut data, newer_fields) =
+            read_versioned_file::<Data>(&path, TEST_MIGRATIONS, None)
+        else {
+            panic!("the file should be read");
+        };
+        data.enabled = false;
+        let fields = to_versioned_fields(&data, TEST_MIGRATIONS, &newer_fields).unwrap();
+        assert_eq!(
+            Value::Object(fields),
+            json!({"schema_version": 7, "enabled": false, "color": "red"})
+        );
+    }
+
+    #[test]
+    fn test_unparsable_file_is_quarantined() {
+        let temp_dir = test_temp_dir!();
+        let path = temp_dir.as_path_untracked().join("data.json");
+        std::fs::write(&path, r#"{"enabled": "yes"}"#).unwrap();
+
+        match read_versioned_file::<Data>(&path, TEST_MIGRATIONS, None) {
+            VersionedFile::Quarantined(fields) => {
+                assert!(!path.exists());
+                let quarantine_path = std::fs::read_dir(temp_dir.as_path_untracked())
+                    .unwrap()
+                    .next()
+                    .unwrap()
+                    .unwrap()
+                    .path();
+                assert!(quarantine_path
+                    .to_str()
+                    .unwrap()
+                    .contains("data.json.corrupt-"));
+                assert_eq!(
+                    std::fs::read_to_string(quarantine_path).unwrap(),
+                    r#"{"enabled": "yes"}"#
+                );
+                assert_eq!(fields.unwrap()["enabled"], Value::from("yes"));
+            }
+            _ => panic!("the file should be quarantined"),
+        }
+
+        std::fs::write(&path, "{").unwrap();
+        assert!(matches!(
+            read_versioned_file::<Data>(&path, TEST_MIGRATIONS, None),
+            VersionedFile::Quarantined(None)
+        ));
+    }
+
+    #[test]
+    fn test_untrusted_file_is_quarantined() {
+        let temp_dir = test_temp_dir!();
+        let path = temp_dir.as_path_untracked().join("data.json");
+        let contents = to_versioned_json(
+            &Data { enabled: true },
+            TEST_MIGRATIONS,
+            &NewerFields::new(),
+        )
+        .unwrap();
+        std::fs::write(&path, contents).unwrap();
+
+        let trusted = |fields: &Map<String, Value>| fields.contains_key("signature");
+        assert!(matches!(
+            read_versioned_file::<Data>(&path, TEST_MIGRATIONS, Some(&trusted)),
+            VersionedFile::Untrusted
+        ));
+        assert!(!path.exists());
+        let name = std::fs::read_dir(temp_dir.as_path_untracked())
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap()
+            .file_name();
+        assert!(name.to_str().unwrap().starts_with("data.json.untrusted-"));
+    }
+}
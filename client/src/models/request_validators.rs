@@ -18,6 +18,7 @@
 
 use anyhow::{anyhow, Context, Result};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::env::consts::ARCH;
 use std::path::PathBuf;
 
@@ -26,7 +27,7 @@ use crate::{
         hardware_info::{table_load_from_device, SystemInfo},
         os_info::{to_debian_architecture, CommandRunner, SystemCommandRunner},
     },
-    constants,
+    constants, helpers,
     models::{
         devices::{Bios, Board, Chassis, PCIPeripheral, Processor, USBPeripheral},
         software::OS,
@@ -88,11 +89,66 @@ pub struct CertificationStatusRequest {
     pub vendor: String,
 }
 
+/// The hardware-only part of a request, as hashed by
+/// `CertificationStatusRequest::fingerprint()`. Changing it requires bumping
+/// `constants::FINGERPRINT_VERSION`.
+#[derive(Serialize)]
+struct HardwareIdentity<'a> {
+    architecture: &'a str,
+    /// Only the vendor of the BIOS, so that firmware updates don't change the
+    /// identity of the machine.
+    bios_vendor: Option<&'a str>,
+    board: &'a Board,
+    chassis: &'a Option<Chassis>,
+    model: &'a str,
+    pci_peripherals: Vec<String>,
+    processor: &'a Processor,
+    usb_peripherals: Vec<String>,
+    vendor: &'a str,
+}
+
+/// Serializes every item and sorts the result, so the order in which the
+/// devices were enumerated doesn't change the fingerprint.
+fn sorted_json<T: Serialize>(items: &[T]) -> Vec<String> {
+    let mut serialized: Vec<String> = items
+        .iter()
+        .map(|item| serde_json::to_string(item).expect("devices are always serializable"))
+        .collect();
+    serialized.sort();
+    serialized
+}
+
 impl CertificationStatusRequest {
     pub fn new(paths: Paths) -> Result<Self> {
         Self::new_with_runner(paths, &SystemCommandRunner)
     }
 
+    /// Returns a stable identifier of the hardware described by this request.
+    ///
+    /// The fingerprint is a SHA-256 digest over the hardware-only fields, so OS,
+    /// kernel and firmware updates don't affect it, prefixed with the
+    /// fingerprint format version, e.g. `v2:5e8a...`.
+    pub fn fingerprint(&self) -> String {
+        let identity = HardwareIdentity {
+            architecture: &self.architecture,
+            bios_vendor: self.bios.as_ref().map(|bios| bios.vendor.as_str()),
+            board: &self.board,
+            chassis: &self.chassis,
+            model: &self.model,
+            pci_peripherals: sorted_json(&self.pci_peripherals),
+            processor: &self.processor,
+            usb_peripherals: sorted_json(&self.usb_peripherals),
+            vendor: &self.vendor,
+        };
+        let canonical =
+            serde_json::to_vec(&identity).expect("hardware identity is always serializable");
+        format!(
+            "v{}:{}",
+            constants::FINGERPRINT_VERSION,
+            helpers::to_hex(&Sha256::digest(canonical))
+        )
+    }
+
     pub(crate) fn new_with_runner(paths: Paths, runner: &impl CommandRunner) -> Result<Self> {
         Self::from(paths, runner)
     }
@@ -216,7 +272,10 @@ mod tests {
     use crate::{
         constants,
         helpers::test_utils::{apply_vars, get_test_filepath, MockCommandRunner},
-        models::request_validators::{CertificationStatusRequest, Paths},
+        models::{
+            devices::{Bios, PCIPeripheral},
+            request_validators::{CertificationStatusRequest, Paths},
+        },
     };
     use serde_json::Value;
     use simple_test_case::test_case;
@@ -310,4 +369,86 @@ mod tests {
 
         assert_eq!(cert_status_request_json, expected_json);
     }
+
+    fn load_test_request(kernel_version: &str) -> CertificationStatusRequest {
+        let content = read_to_string(get_test_filepath("amd64/dell_xps13/request.json")).unwrap();
+        let content = apply_vars(
+            content,
+            &[
+                ("CODENAME", "noble"),
+                ("KERNEL_VERSION", kernel_version),
+                ("KERNEL_MODULES", "[\"nvme\"]"),
+                ("RELEASE", "24.04"),
+            ],
+        );
+        serde_json::from_str(&content).unwrap()
+    }
+
+    fn create_pci_peripheral(pci_id: &str) -> PCIPeripheral {
+        PCIPeripheral {
+            pci_id: pci_id.to_string(),
+            name: "".to_string(),
+            status: None,
+            vendor: "".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_fingerprint_is_versioned() {
+        let fingerprint = load_test_request("6.8.0-1013-oem").fingerprint();
+        assert!(fingerprint.starts_with(&format!("v{}:", constants::FINGERPRINT_VERSION)));
+        assert_eq!(
+            fingerprint,
+            load_test_request("6.8.0-1013-oem").fingerprint()
+        );
+    }
+
+    #[test]
+    fn test_fingerprint_ignores_software() {
+        let request = load_test_request("6.8.0-1013-oem");
+        let mut upgraded = load_test_request("6.8.0-1020-oem");
+        upgraded.os.kernel.loaded_modules.push("zfs".to_string());
+        assert_eq!(request.fingerprint(), upgraded.fingerprint());
+    }
+
+    #[test]
+    fn test_fingerprint_tracks_hardware() {
+        let request = load_test_request("6.8.0-1013-oem");
+        let mut swapped = request.clone();
+        swapped.board.product_name = "0KRNM1".to_string();
+        assert_ne!(request.fingerprint(), swapped.fingerprint());
+    }
+
+    #[test]
+    fn test_fingerprint_ignores_firmware_updates() {
+        let mut request = load_test_request("6.8.0-1013-oem");
+        request.bios = Some(Bios {
+            firmware_revision: Some("1.2".to_string()),
+            release_date: Some("2024-01-10".to_string()),
+            revision: Some("1.20".to_string()),
+            vendor: "Dell Inc.".to_string(),
+            version: "1.20.0".to_string(),
+        });
+        let mut updated = request.clone();
+        updated.bios = Some(Bios {
+            firmware_revision: Some("1.3".to_string()),
+            release_date: Some("2024-06-02".to_string()),
+            revision: Some("1.21".to_string()),
+            vendor: "Dell Inc.".to_string(),
+            version: "1.21.0".to_string(),
+        });
+        assert_eq!(request.fingerprint(), updated.fingerprint());
+    }
+
+    #[test]
+    fn test_fingerprint_ignores_peripheral_order() {
+        let mut request = load_test_request("6.8.0-1013-oem");
+        request.pci_peripherals = vec![
+            create_pci_peripheral("8086:7e40"),
+            create_pci_peripheral("8086:7e7d"),
+        ];
+        let mut reordered = request.clone();
+        reordered.pci_peripherals.reverse();
+        assert_eq!(request.fingerprint(), reordered.fingerprint());
+    }
 }
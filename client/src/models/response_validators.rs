@@ -26,7 +26,7 @@ use crate::models::{
     software::OS,
 };
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(tag = "status")]
 pub enum CertificationStatusResponse {
     Certified {
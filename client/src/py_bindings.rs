@@ -17,15 +17,21 @@
  */
 
 use crate::{
-    check_certification_status as native_check_certification_status,
+    check_certification_status_with_transport as native_check_certification_status,
     models::request_validators::{CertificationStatusRequest, Paths},
-    CheckCertificationSource,
+    spawn_refresh, CheckCertificationSource, HttpTransport, Integration, Transport,
 };
 use pyo3::{
     exceptions::PyRuntimeError, prelude::*, types::PyString, wrap_pyfunction, Py, PyAny, PyResult,
     Python,
 };
 use serde_json;
+use std::sync::Arc;
+
+/// Identifies the requests of the Python bindings to the server.
+fn python_transport() -> Option<Arc<dyn Transport>> {
+    Some(Arc::new(HttpTransport::new(Integration::Python)))
+}
 
 /// This function creates and sends the certification status request to the specified
 /// hardware-api server URL. It keeps backward compatibility with the previous version of the function that returned a JSON string.
@@ -39,6 +45,7 @@ fn send_certification_request(py: Python, url: String) -> PyResult<Py<PyAny>> {
         CheckCertificationSource::Server,
         &request_body,
         None,
+        python_transport(),
     );
 
     if response.is_err() {
@@ -67,15 +74,18 @@ fn send_certification_request(py: Python, url: String) -> PyResult<Py<PyAny>> {
 /// Normal mode is the default mode, which checks the cache first and then queries the server if needed.
 /// Forced mode always queries the server and updates the cache.
 /// Cached mode returns always the cache and does not query the server.
+/// Background mode returns the cache at once and, if it must be refreshed,
+/// queries the server in a background thread.
 #[pyfunction]
 fn check_certification_status(py: Python, url: String, mode: String) -> PyResult<Py<PyAny>> {
     let mode = match mode.as_str() {
         "server" => CheckCertificationSource::Server,
         "cache" => CheckCertificationSource::Cache,
         "auto" => CheckCertificationSource::Auto,
+        "background" => CheckCertificationSource::Background,
         _ => {
             return Err(PyRuntimeError::new_err(format!(
-                "Invalid mode: {}. Valid modes are 'auto', 'server' and 'cache'.",
+                "Invalid mode: {}. Valid modes are 'auto', 'server', 'cache' and 'background'.",
                 mode
             )))
         }
@@ -83,7 +93,13 @@ fn check_certification_status(py: Python, url: String, mode: String) -> PyResult
     let request_body = CertificationStatusRequest::new(Paths::default())
         .map_err(|e| PyRuntimeError::new_err(format!("failed to create request: {e}")))?;
 
-    let response = native_check_certification_status(url, mode, &request_body, None);
+    let response = native_check_certification_status(
+        url.clone(),
+        mode,
+        &request_body,
+        None,
+        python_transport(),
+    );
 
     if response.is_err() {
         let e = response.err().unwrap();
@@ -92,6 +108,9 @@ fn check_certification_status(py: Python, url: String, mode: String) -> PyResult
         )));
     }
     let response = response.unwrap();
+    if response.refresh_pending() {
+        spawn_refresh(url, request_body, None, python_transport());
+    }
 
     let json_str = serde_json::json!(response).to_string();
     let json = PyString::new(py, &json_str);
@@ -0,0 +1,299 @@
+/* Copyright 2026 Canonical Ltd.
+ *
+ * This program is free software: you can redistribute it and/or
+ * modify it under the terms of the GNU Lesser General Public License
+ * version 3, as published by the Free Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Lesser General Public License for more details.
+ *
+ * You should have received a copy of the GNU Lesser General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use anyhow::{anyhow, Result};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::sync::OnceLock;
+
+use crate::{constants, helpers, models::request_validators::CertificationStatusRequest};
+
+type HmacSha256 = Hmac<Sha256>;
+
+const HASH_PREFIX: &str = "hmac-sha256:";
+
+/// Fields of the collected hardware data that can identify a single machine
+/// rather than a hardware model.
+///
+/// Asset tags and MAC-derived identifiers are not collected yet. They must be
+/// added here, and covered by every preset, together with their collectors.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RedactedField {
+    ChassisSku,
+    BoardVersion,
+}
+
+/// What happens to a field before it leaves the collecting code.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RedactionAction {
+    /// The value is sent and cached as collected.
+    Keep,
+    /// The value is stripped from the data sent to the server, and replaced
+    /// in the cache by its HMAC-SHA256 keyed with a secret derived from the
+    /// machine ID, so that changes are still detected. The hash identifies
+    /// the machine, so it never leaves it. Stripped from the cache too if
+    /// there is no machine ID.
+    Hash,
+    /// The value is replaced by an empty string.
+    Strip,
+}
+
+/// Named redaction presets, applied to the hardware data before it is sent
+/// to the server, see `apply_for_server`, and before it is stored in the
+/// cache, see `apply`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RedactionPolicy {
+    /// The hardware data is sent as collected.
+    #[default]
+    None,
+    /// Serial-like fields aren't sent, and are hashed in the cache.
+    Minimal,
+    /// Serial-like fields are removed.
+    Full,
+}
+
+impl RedactionPolicy {
+    /// Returns the preset with the given name: "none", "minimal" or "full".
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "none" => Ok(RedactionPolicy::None),
+            "minimal" => Ok(RedactionPolicy::Minimal),
+            "full" => Ok(RedactionPolicy::Full),
+            _ => Err(anyhow!(
+                "Invalid redaction policy: {}. Valid policies are 'none', 'minimal' and 'full'.",
+                name
+            )),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RedactionPolicy::None => "none",
+            RedactionPolicy::Minimal => "minimal",
+            RedactionPolicy::Full => "full",
+        }
+    }
+
+    /// Returns the action this policy applies to the given field.
+    pub fn action(&self, field: RedactedField) -> RedactionAction {
+        match (self, field) {
+            (RedactionPolicy::None, _) => RedactionAction::Keep,
+            (RedactionPolicy::Minimal, _) => RedactionAction::Hash,
+            (RedactionPolicy::Full, _) => RedactionAction::Strip,
+        }
+    }
+
+    /// Lists every redactable field together with the action applied to it
+    /// on this machine, so the policy can be audited.
+    pub fn rules(&self) -> Vec<(RedactedField, RedactionAction)> {
+        [RedactedField::ChassisSku, RedactedField::BoardVersion]
+            .into_iter()
+            .map(|field| match self.action(field) {
+                RedactionAction::Hash if machine_secret().is_none() => {
+                    (field, RedactionAction::Strip)
+                }
+                action => (field, action),
+            })
+            .collect()
+    }
+
+    /// Returns a copy of the hardware data with this policy applied, to be
+    /// stored in the cache.
+    pub fn apply(&self, hardware_data: &CertificationStatusRequest) -> CertificationStatusRequest {
+        self.apply_with_secret(hardware_data, machine_secret())
+    }
+
+    /// Returns a copy of the hardware data with this policy applied, to be
+    /// sent to the server: hashed fields are stripped, since a hash that never
+    /// changes would let the server tell machines apart.
+    pub fn apply_for_server(
+        &self,
+        hardware_data: &CertificationStatusRequest,
+    ) -> CertificationStatusRequest {
+        self.apply_with_secret(hardware_data, None)
+    }
+
+    fn apply_with_secret(
+        &self,
+        hardware_data: &CertificationStatusRequest,
+        secret: Option<&[u8]>,
+    ) -> CertificationStatusRequest {
+        let mut redacted = hardware_data.clone();
+        if let Some(chassis) = redacted.chassis.as_mut() {
+            redact_value(
+                &mut chassis.sku,
+                self.action(RedactedField::ChassisSku),
+                secret,
+            );
+        }
+        redact_value(
+            &mut redacted.board.version,
+            self.action(RedactedField::BoardVersion),
+            secret,
+        );
+        redacted
+    }
+}
+
+/// Returns the key serial-like fields are hashed with, derived from the
+/// machine ID like systemd's application-specific IDs, so that the machine ID
+/// itself can't be inferred from it. None if there is no machine ID.
+fn machine_secret() -> Option<&'static [u8]> {
+    static SECRET: OnceLock<Option<Vec<u8>>> = OnceLock::new();
+    SECRET
+        .get_or_init(|| {
+            let machine_id = std::fs::read_to_string(constants::MACHINE_ID_PATH).ok()?;
+            let machine_id = machine_id.trim();
+            if machine_id.is_empty() {
+                return None;
+            }
+            let mut mac = HmacSha256::new_from_slice(machine_id.as_bytes()).ok()?;
+            mac.update(constants::REDACTION_KEY_CONTEXT.as_bytes());
+            Some(mac.finalize().into_bytes().to_vec())
+        })
+        .as_deref()
+}
+
+fn redact_value(value: &mut String, action: RedactionAction, secret: Option<&[u8]>) {
+    match (action, secret) {
+        (RedactionAction::Keep, _) => {}
+        (RedactionAction::Hash, Some(secret)) => {
+            let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts any key length");
+            mac.update(value.as_bytes());
+            let tag = mac.finalize().into_bytes();
+            *value = format!("{}{}", HASH_PREFIX, helpers::to_hex(&tag));
+        }
+        // Without a secret, a hash of a short value could be reversed.
+        (RedactionAction::Hash, None) | (RedactionAction::Strip, _) => value.clear(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{
+        devices::{Board, Chassis, Processor},
+        software::{KernelPackage, OS},
+    };
+
+    fn create_test_hardware_data() -> CertificationStatusRequest {
+        CertificationStatusRequest {
+            architecture: "amd64".to_string(),
+            bios: None,
+            board: Board {
+                manufacturer: "Dell Inc.".to_string(),
+                product_name: "0KRNM1".to_string(),
+                version: "A01".to_string(),
+            },
+            chassis: Some(Chassis {
+                chassis_type: "Notebook".to_string(),
+                manufacturer: "Dell Inc.".to_string(),
+                sku: "0AF0".to_string(),
+                version: "".to_string(),
+            }),
+            model: "XPS 13 9310".to_string(),
+            os: OS {
+                codename: "noble".to_string(),
+                distributor: "Ubuntu".to_string(),
+                version: "24.04".to_string(),
+                kernel: KernelPackage {
+                    name: None,
+                    version: "6.8.0-1013-oem".to_string(),
+                    signature: None,
+                    loaded_modules: vec![],
+                },
+            },
+            pci_peripherals: vec![],
+            processor: Processor {
+                identifier: None,
+                frequency: 0,
+                version: "".to_string(),
+                manufacturer: "".to_string(),
+            },
+            usb_peripherals: vec![],
+            vendor: "Dell Inc.".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_none_keeps_data() {
+        let hardware_data = create_test_hardware_data();
+        assert_eq!(RedactionPolicy::None.apply(&hardware_data), hardware_data);
+    }
+
+    #[test]
+    fn test_minimal_hashes_serial_like_fields() {
+        let hardware_data = create_test_hardware_data();
+        let redacted = RedactionPolicy::Minimal.apply_with_secret(&hardware_data, Some(b"one"));
+        assert_eq!(
+            redacted.chassis.unwrap().sku,
+            "hmac-sha256:4cad0077ffaf429ee5ab8512ea39cac52839f972eead46fe019fc5a189ab7c0c"
+        );
+        assert!(redacted.board.version.starts_with(HASH_PREFIX));
+        assert_eq!(redacted.board.product_name, "0KRNM1");
+        assert_eq!(redacted.model, "XPS 13 9310");
+
+        // The hashes depend on the machine secret.
+        let other = RedactionPolicy::Minimal.apply_with_secret(&hardware_data, Some(b"two"));
+        assert_ne!(other.board.version, redacted.board.version);
+    }
+
+    #[test]
+    fn test_minimal_strips_without_secret() {
+        let redacted =
+            RedactionPolicy::Minimal.apply_with_secret(&create_test_hardware_data(), None);
+        assert_eq!(redacted.chassis.unwrap().sku, "");
+        assert_eq!(redacted.board.version, "");
+    }
+
+    #[test]
+    fn test_minimal_sends_nothing_identifying() {
+        let hardware_data = create_test_hardware_data();
+        let sent = RedactionPolicy::Minimal.apply_for_server(&hardware_data);
+        assert_eq!(sent.chassis.as_ref().unwrap().sku, "");
+        assert_eq!(sent.board.version, "");
+        assert_eq!(sent, RedactionPolicy::Full.apply_for_server(&hardware_data));
+        // Unlike what is cached, which must still detect changes.
+        assert_ne!(RedactionPolicy::Minimal.apply(&hardware_data), sent);
+    }
+
+    #[test]
+    fn test_full_strips_serial_like_fields() {
+        let redacted = RedactionPolicy::Full.apply(&create_test_hardware_data());
+        assert_eq!(redacted.chassis.unwrap().sku, "");
+        assert_eq!(redacted.board.version, "");
+        assert_eq!(redacted.vendor, "Dell Inc.");
+    }
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(
+            RedactionPolicy::from_name("full").unwrap(),
+            RedactionPolicy::Full
+        );
+        assert!(RedactionPolicy::from_name("everything").is_err());
+        for policy in [
+            RedactionPolicy::None,
+            RedactionPolicy::Minimal,
+            RedactionPolicy::Full,
+        ] {
+            assert_eq!(RedactionPolicy::from_name(policy.name()).unwrap(), policy);
+        }
+    }
+}
@@ -0,0 +1,290 @@
+/* Copyright 2026 Canonical Ltd.
+ *
+ * This program is free software: you can redistribute it and/or
+ * modify it under the terms of the GNU Lesser General Public License
+ * version 3, as published by the Free Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Lesser General Public License for more details.
+ *
+ * You should have received a copy of the GNU Lesser General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Access to the files of the cache, the settings and the keys.
+//!
+//! The files may be shared between the daemon, the Python bindings and
+//! library users, so changes are made under an advisory lock and written
+//! atomically. Files holding secrets or trusted data are only read if they
+//! are regular files that other users can't modify.
+
+use anyhow::{bail, Context, Result};
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{Error as IoError, ErrorKind, Read, Write};
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// An advisory lock, released when dropped.
+pub(crate) struct FileLock {
+    _file: Option<File>,
+}
+
+/// Takes an exclusive advisory lock on the file at `path`, creating it if
+/// needed, waiting at most `timeout` for other processes to release it.
+///
+/// Users who can't write the lock file, e.g. non-root users of the files of
+/// the daemon, can't write the locked files either, so they only read them:
+/// they get a shared lock if the lock file exists, so that they don't read
+/// in the middle of a change, and no lock otherwise.
+pub(crate) fn lock_file(path: &Path, timeout: Duration) -> Result<FileLock> {
+    let opened = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .mode(0o644)
+        .open(path);
+    let (file, shared) = match opened {
+        Ok(file) => (file, false),
+        Err(error)
+            if matches!(
+                error.kind(),
+                ErrorKind::PermissionDenied | ErrorKind::ReadOnlyFilesystem
+            ) =>
+        {
+            match File::open(path) {
+                Ok(file) => (file, true),
+                Err(_) => return Ok(FileLock { _file: None }),
+            }
+        }
+        Err(error) => {
+            return Err(error).with_context(|| format!("cannot open lock file {}", path.display()))
+        }
+    };
+    wait_for_lock(file, shared, path, timeout)
+}
+
+fn wait_for_lock(file: File, shared: bool, path: &Path, timeout: Duration) -> Result<FileLock> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let locked = match shared {
+            true => file.try_lock_shared(),
+            false => file.try_lock(),
+        };
+        match locked {
+            Ok(()) => return Ok(FileLock { _file: Some(file) }),
+            Err(TryLockError::WouldBlock) if Instant::now() < deadline => {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            Err(TryLockError::WouldBlock) => {
+                return Err(IoError::from(ErrorKind::TimedOut))
+                    .with_context(|| format!("timed out waiting for lock {}", path.display()));
+            }
+            Err(TryLockError::Error(error)) => {
+                return Err(error).with_context(|| format!("cannot lock {}", path.display()));
+            }
+        }
+    }
+}
+
+/// Returns true if files owned by `uid` can be trusted by this process: those
+/// of root and of the effective user, who could write them anyway.
+pub(crate) fn is_trusted_owner(uid: u32) -> bool {
+    // SAFETY: geteuid() has no preconditions and can't fail.
+    uid == 0 || uid == unsafe { libc::geteuid() }
+}
+
+/// Returns the contents of a file holding keys or credentials, refusing it if
+/// it is a symbolic link, isn't owned by root or the effective user, or other
+/// users can modify it or, for `private` files, read it.
+///
+/// The checks are made on the opened file, so that it can't be replaced
+/// between the checks and the read.
+pub(crate) fn read_protected_file(path: &Path, private: bool) -> Result<Vec<u8>> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NOFOLLOW)
+        .open(path)
+        .with_context(|| format!("cannot read {}", path.display()))?;
+    let metadata = file
+        .metadata()
+        .with_context(|| format!("cannot read {}", path.display()))?;
+    if !metadata.is_file() {
+        bail!("{} isn't a regular file", path.display());
+    }
+    if !is_trusted_owner(metadata.uid()) {
+        bail!(
+            "{} must be owned by root or the current user (owner {})",
+            path.display(),
+            metadata.uid()
+        );
+    }
+    let (forbidden_mode, access) = match private {
+        true => (0o077, "accessible"),
+        false => (0o022, "writable"),
+    };
+    if metadata.mode() & forbidden_mode != 0 {
+        bail!(
+            "{} must not be {} by other users (mode {:o})",
+            path.display(),
+            access,
+            metadata.mode() & 0o777
+        );
+    }
+    let mut contents = Vec::new();
+    file.read_to_end(&mut contents)
+        .with_context(|| format!("cannot read {}", path.display()))?;
+    Ok(contents)
+}
+
+/// Replaces the file at `path` with `contents`, so that after a crash or a
+/// full disk either the old or the new contents are found, never a truncated
+/// file.
+///
+/// The data is written to a temporary file in the same folder, created with
+/// `mode` from the start, flushed to disk and then renamed over `path`.
+pub(crate) fn write_file_atomically(path: &Path, contents: &[u8], mode: u32) -> Result<()> {
+    let folder = path
+        .parent()
+        .filter(|folder| !folder.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let mut temp_name = path
+        .file_name()
+        .with_context(|| format!("invalid file path: {}", path.display()))?
+        .to_os_string();
+    temp_name.push(format!(".tmp-{}", std::process::id()));
+    let temp_path = folder.join(temp_name);
+
+    let result = (|| -> Result<()> {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(mode)
+            .custom_flags(libc::O_NOFOLLOW)
+            .open(&temp_path)
+            .with_context(|| format!("cannot create {}", temp_path.display()))?;
+        file.write_all(contents)
+            .with_context(|| format!("cannot write {}", temp_path.display()))?;
+        file.sync_all()
+            .with_context(|| format!("cannot flush {}", temp_path.display()))?;
+        std::fs::rename(&temp_path, path)
+            .with_context(|| format!("cannot replace {}", path.display()))?;
+        // Make the rename itself durable.
+        File::open(folder)
+            .and_then(|folder| folder.sync_all())
+            .with_context(|| format!("cannot flush {}", folder.display()))
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use test_temp_dir::test_temp_dir;
+
+    #[test]
+    fn test_write_file_atomically() {
+        let temp_dir = test_temp_dir!();
+        let path = temp_dir.as_path_untracked().join("data.json");
+
+        write_file_atomically(&path, b"first", 0o600).unwrap();
+        write_file_atomically(&path, b"second", 0o600).unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "second");
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        // No temporary files are left behind.
+        assert_eq!(
+            std::fs::read_dir(temp_dir.as_path_untracked())
+                .unwrap()
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_lock_file_times_out() {
+        let temp_dir = test_temp_dir!();
+        let path = temp_dir.as_path_untracked().join("data.lock");
+
+        let lock = lock_file(&path, Duration::from_secs(1)).unwrap();
+        let error = lock_file(&path, Duration::from_millis(50)).err().unwrap();
+        assert_eq!(
+            error.downcast_ref::<IoError>().unwrap().kind(),
+            ErrorKind::TimedOut
+        );
+
+        drop(lock);
+        assert!(lock_file(&path, Duration::from_millis(50)).is_ok());
+    }
+
+    #[test]
+    fn test_shared_lock_waits_for_changes() {
+        let temp_dir = test_temp_dir!();
+        let path = temp_dir.as_path_untracked().join("data.lock");
+
+        // What users who can't write the lock file get.
+        let lock = lock_file(&path, Duration::from_secs(1)).unwrap();
+        let reader = File::open(&path).unwrap();
+        assert!(wait_for_lock(reader, true, &path, Duration::from_millis(50)).is_err());
+        drop(lock);
+
+        let reader = File::open(&path).unwrap();
+        let shared = wait_for_lock(reader, true, &path, Duration::from_millis(50)).unwrap();
+        let reader = File::open(&path).unwrap();
+        assert!(wait_for_lock(reader, true, &path, Duration::from_millis(50)).is_ok());
+        assert!(lock_file(&path, Duration::from_millis(50)).is_err());
+        drop(shared);
+    }
+
+    #[test]
+    fn test_read_protected_file() {
+        let temp_dir = test_temp_dir!();
+        let path = temp_dir.as_path_untracked().join("key");
+        write_file_atomically(&path, b"secret", 0o600).unwrap();
+        assert_eq!(read_protected_file(&path, true).unwrap(), b"secret");
+
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        assert!(read_protected_file(&path, true).is_err());
+        assert_eq!(read_protected_file(&path, false).unwrap(), b"secret");
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o666)).unwrap();
+        assert!(read_protected_file(&path, false).is_err());
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+
+        let link = temp_dir.as_path_untracked().join("link");
+        std::os::unix::fs::symlink(&path, &link).unwrap();
+        assert!(read_protected_file(&link, true).is_err());
+        assert!(read_protected_file(temp_dir.as_path_untracked(), false).is_err());
+
+        // Only root can give files away, to check the owner.
+        if std::os::unix::fs::chown(&path, Some(65534), None).is_ok() {
+            let error = read_protected_file(&path, true).unwrap_err();
+            assert!(
+                error
+                    .to_string()
+                    .contains("must be owned by root or the current user"),
+                "{}",
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn test_write_file_atomically_keeps_old_file_on_error() {
+        let temp_dir = test_temp_dir!();
+        let path = temp_dir.as_path_untracked().join("data.json");
+        write_file_atomically(&path, b"first", 0o600).unwrap();
+
+        let missing_folder = temp_dir.as_path_untracked().join("missing/data.json");
+        assert!(write_file_atomically(&missing_folder, b"second", 0o600).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "first");
+    }
+}
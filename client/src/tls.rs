@@ -0,0 +1,337 @@
+/* Copyright 2026 Canonical Ltd.
+ *
+ * This program is free software: you can redistribute it and/or
+ * modify it under the terms of the GNU Lesser General Public License
+ * version 3, as published by the Free Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Lesser General Public License for more details.
+ *
+ * You should have received a copy of the GNU Lesser General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! TLS configuration used to reach the server.
+//!
+//! Besides the system trust store, the certificates of a CA bundle can be
+//! trusted, for private deployments of the server. The public keys of the
+//! server can also be pinned: the connection then fails unless the server
+//! certificate holds one of them, even if a trusted CA signed it. Servers
+//! that require mutual TLS are presented a client certificate.
+
+use anyhow::{bail, Context, Result};
+use native_tls::{Certificate, HandshakeError, Identity};
+use openssl::x509::X509;
+use sha2::{Digest, Sha256};
+use std::io::{Error as IoError, ErrorKind};
+use std::path::{Path, PathBuf};
+use ureq::ReadWrite;
+
+use crate::{helpers, storage};
+
+const PIN_PREFIX: &str = "sha256:";
+const PEM_BEGIN: &str = "-----BEGIN CERTIFICATE-----";
+const PEM_END: &str = "-----END CERTIFICATE-----";
+
+/// Returns true if `pin` is a SHA-256 digest in the `sha256:<hex>` format.
+pub(crate) fn is_valid_pin(pin: &str) -> bool {
+    pin.strip_prefix(PIN_PREFIX)
+        .is_some_and(|hex| hex.len() == 64 && hex.bytes().all(|byte| byte.is_ascii_hexdigit()))
+}
+
+/// Returns the pin of the public key of a DER certificate: the SHA-256 of its
+/// SubjectPublicKeyInfo, as computed by
+/// `openssl x509 -pubkey -noout | openssl pkey -pubin -outform der | sha256sum`.
+pub(crate) fn public_key_pin(certificate: &[u8]) -> Option<String> {
+    let public_key = X509::from_der(certificate)
+        .and_then(|certificate| certificate.public_key())
+        .and_then(|public_key| public_key.public_key_to_der())
+        .ok()?;
+    let digest = Sha256::digest(public_key);
+    Some(format!("{}{}", PIN_PREFIX, helpers::to_hex(&digest)))
+}
+
+/// Returns the certificates of a PEM bundle.
+fn load_ca_bundle(path: &Path) -> Result<Vec<Certificate>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("cannot read the CA bundle {}", path.display()))?;
+    let mut certificates = Vec::new();
+    let mut rest = contents.as_str();
+    while let Some(start) = rest.find(PEM_BEGIN) {
+        let Some(end) = rest[start..].find(PEM_END) else {
+            break;
+        };
+        let end = start + end + PEM_END.len();
+        let certificate = Certificate::from_pem(&rest.as_bytes()[start..end])
+            .with_context(|| format!("invalid certificate in {}", path.display()))?;
+        certificates.push(certificate);
+        rest = &rest[end..];
+    }
+    if certificates.is_empty() {
+        bail!("no certificate found in {}", path.display());
+    }
+    Ok(certificates)
+}
+
+/// The error of a TLS handshake that failed, or whose server certificate was
+/// refused.
+#[derive(Debug)]
+pub(crate) struct TlsFailure(String);
+
+impl std::fmt::Display for TlsFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for TlsFailure {}
+
+/// How the server is authenticated, and how the client authenticates to it.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct TlsSettings {
+    /// PEM file with the certificates of CAs to trust besides the system ones.
+    pub(crate) ca_bundle: Option<PathBuf>,
+    /// Public keys the server must present, in the `sha256:<hex>` format.
+    pub(crate) pinned_public_keys: Vec<String>,
+    /// PEM certificate and PKCS#8 key presented to the server.
+    pub(crate) client_certificate: Option<(PathBuf, PathBuf)>,
+}
+
+/// Returns the client identity made of a PEM certificate, which may be
+/// followed by intermediate certificates, and its PKCS#8 PEM key. Both must
+/// be owned by root, not be symbolic links and not be writable, or for the
+/// key readable, by other users.
+fn load_identity(certificate: &Path, key: &Path) -> Result<Identity> {
+    let certificate = storage::read_protected_file(certificate, false)?;
+    let key = storage::read_protected_file(key, true)?;
+    Identity::from_pkcs8(&certificate, &key).context("invalid client certificate or key")
+}
+
+/// Connector that trusts the system store and an optional CA bundle, presents
+/// the client certificate, if any, and checks the pinned public keys, if any,
+/// once the handshake is done.
+pub(crate) struct TlsConnector {
+    connector: native_tls::TlsConnector,
+    pinned_public_keys: Vec<String>,
+}
+
+impl TlsConnector {
+    pub(crate) fn new(settings: &TlsSettings) -> Result<Self> {
+        let mut builder = native_tls::TlsConnector::builder();
+        if let Some(ca_bundle) = &settings.ca_bundle {
+            for certificate in load_ca_bundle(ca_bundle)? {
+                builder.add_root_certificate(certificate);
+            }
+        }
+        if let Some((certificate, key)) = &settings.client_certificate {
+            builder.identity(load_identity(certificate, key)?);
+        }
+        Ok(TlsConnector {
+            connector: builder.build()?,
+            pinned_public_keys: settings.pinned_public_keys.clone(),
+        })
+    }
+
+    /// Returns true if the certificate presented by the server holds one of
+    /// the pinned public keys, or if there are none.
+    fn is_pinned(&self, certificate: Option<Certificate>) -> bool {
+        if self.pinned_public_keys.is_empty() {
+            return true;
+        }
+        certificate
+            .and_then(|certificate| certificate.to_der().ok())
+            .and_then(|certificate| public_key_pin(&certificate))
+            .is_some_and(|pin| {
+                self.pinned_public_keys
+                    .iter()
+                    .any(|pinned| pinned.eq_ignore_ascii_case(&pin))
+            })
+    }
+}
+
+impl ureq::TlsConnector for TlsConnector {
+    fn connect(
+        &self,
+        dns_name: &str,
+        io: Box<dyn ReadWrite>,
+    ) -> Result<Box<dyn ReadWrite>, ureq::Error> {
+        let stream = self
+            .connector
+            .connect(dns_name, io)
+            .map_err(|error| match error {
+                HandshakeError::Failure(error) => {
+                    IoError::new(ErrorKind::ConnectionRefused, TlsFailure(error.to_string()))
+                }
+                HandshakeError::WouldBlock(_) => {
+                    IoError::new(ErrorKind::TimedOut, "TLS handshake timed out")
+                }
+            })?;
+        if !self.is_pinned(stream.peer_certificate().ok().flatten()) {
+            let message = format!(
+                "the public key of {} doesn't match the pinned keys",
+                dns_name
+            );
+            return Err(IoError::new(ErrorKind::PermissionDenied, TlsFailure(message)).into());
+        }
+        Ok(Box::new(stream))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::test_utils::get_test_filepath;
+    use crate::transport::{from_ureq_error, TransportError};
+    use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod, SslVerifyMode};
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::os::unix::fs::PermissionsExt;
+    use std::sync::Arc;
+    use test_temp_dir::test_temp_dir;
+
+    const SERVER_PIN: &str =
+        "sha256:bc4a985b293291fc911ae3b8191cca2504d088c0e8ddfb9a0ba695a87f775cb4";
+
+    /// Starts a local HTTPS server with the test certificate, which answers
+    /// `ok` to every request, and returns its URL. If `client_ca` is set,
+    /// clients must present a certificate signed by it.
+    fn start_server(client_ca: Option<&Path>) -> String {
+        let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls_server()).unwrap();
+        acceptor
+            .set_certificate_chain_file(get_test_filepath("tls/server.pem"))
+            .unwrap();
+        acceptor
+            .set_private_key_file(get_test_filepath("tls/server.key"), SslFiletype::PEM)
+            .unwrap();
+        if let Some(client_ca) = client_ca {
+            acceptor.set_ca_file(client_ca).unwrap();
+            acceptor.set_verify(SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT);
+        }
+        let acceptor = acceptor.build();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                // Handshakes rejected by either side are expected to fail.
+                let Ok(mut stream) = acceptor.accept(stream.unwrap()) else {
+                    continue;
+                };
+                let mut request = [0u8; 4096];
+                let _ = stream.read(&mut request);
+                let _ = stream.write_all(
+                    b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok",
+                );
+            }
+        });
+        format!("https://localhost:{}/", port)
+    }
+
+    fn get(url: &str, settings: &TlsSettings) -> Result<String, TransportError> {
+        let connector = TlsConnector::new(settings).unwrap();
+        let agent = ureq::AgentBuilder::new()
+            .tls_connector(Arc::new(connector))
+            .build();
+        let response = agent.get(url).call().map_err(from_ureq_error)?;
+        Ok(response.into_string().unwrap())
+    }
+
+    fn trusting_test_ca() -> TlsSettings {
+        TlsSettings {
+            ca_bundle: Some(get_test_filepath("tls/ca.pem")),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_public_key_pin() {
+        let certificate = std::fs::read(get_test_filepath("tls/server.pem")).unwrap();
+        let certificate = Certificate::from_pem(&certificate).unwrap();
+        assert_eq!(
+            public_key_pin(&certificate.to_der().unwrap()),
+            Some(SERVER_PIN.to_string())
+        );
+        assert_eq!(public_key_pin(b"\x30\x03\x02\x01"), None);
+        assert!(is_valid_pin(SERVER_PIN));
+        assert!(!is_valid_pin("sha256:bc4a98"));
+        assert!(!is_valid_pin(&SERVER_PIN.replace("sha256:", "sha1:")));
+    }
+
+    #[test]
+    fn test_ca_bundle_and_pinning() {
+        let url = start_server(None);
+
+        // The test CA isn't in the system store.
+        assert!(matches!(
+            get(&url, &TlsSettings::default()),
+            Err(TransportError::Tls(_))
+        ));
+        assert_eq!(get(&url, &trusting_test_ca()).unwrap(), "ok");
+
+        // A pinned key must match, even when the CA is trusted.
+        let wrong_pin = format!("sha256:{}", "0".repeat(64));
+        let mut settings = trusting_test_ca();
+        settings.pinned_public_keys = vec![wrong_pin.clone(), SERVER_PIN.to_string()];
+        assert_eq!(get(&url, &settings).unwrap(), "ok");
+        settings.pinned_public_keys = vec![wrong_pin];
+        let error = get(&url, &settings).unwrap_err();
+        assert!(matches!(error, TransportError::Tls(_)), "{}", error);
+        assert!(error.to_string().contains("pinned"), "{}", error);
+
+        settings.ca_bundle = Some(get_test_filepath("tls/missing.pem"));
+        assert!(TlsConnector::new(&settings).is_err());
+        settings.ca_bundle = Some(get_test_filepath("tls/server.key"));
+        assert!(TlsConnector::new(&settings).is_err());
+    }
+
+    #[test]
+    fn test_client_certificate() {
+        let temp_dir = test_temp_dir!();
+        let client_certificate = get_test_filepath("tls/client.pem");
+        let url = start_server(Some(&client_certificate));
+        // git doesn't keep the permissions of the key, so it's copied.
+        let key = temp_dir.as_path_untracked().join("client.key");
+        std::fs::copy(get_test_filepath("tls/client.key"), &key).unwrap();
+        std::fs::set_permissions(&key, std::fs::Permissions::from_mode(0o600)).unwrap();
+
+        let mut settings = trusting_test_ca();
+        assert!(get(&url, &settings).is_err());
+        settings.client_certificate = Some((client_certificate.clone(), key.clone()));
+        assert_eq!(get(&url, &settings).unwrap(), "ok");
+
+        // A key that other users can read is refused.
+        std::fs::set_permissions(&key, std::fs::Permissions::from_mode(0o640)).unwrap();
+        let error = TlsConnector::new(&settings).err().unwrap();
+        assert!(
+            error.to_string().contains("must not be accessible"),
+            "{}",
+            error
+        );
+
+        std::fs::set_permissions(&key, std::fs::Permissions::from_mode(0o600)).unwrap();
+
+        // So is a link to the key, which could be replaced after the check.
+        let link = temp_dir.as_path_untracked().join("link.key");
+        std::os::unix::fs::symlink(&key, &link).unwrap();
+        settings.client_certificate = Some((client_certificate.clone(), link));
+        assert!(TlsConnector::new(&settings).is_err());
+
+        // And a key owned by another user, which only root can create.
+        if std::os::unix::fs::chown(&key, Some(65534), None).is_ok() {
+            settings.client_certificate = Some((client_certificate.clone(), key.clone()));
+            let error = TlsConnector::new(&settings).err().unwrap();
+            assert!(
+                error
+                    .to_string()
+                    .contains("must be owned by root or the current user"),
+                "{}",
+                error
+            );
+            std::os::unix::fs::chown(&key, Some(0), None).unwrap();
+        }
+
+        settings.client_certificate = Some((temp_dir.as_path_untracked().into(), key));
+        assert!(TlsConnector::new(&settings).is_err());
+    }
+}
@@ -0,0 +1,739 @@
+/* Copyright 2026 Canonical Ltd.
+ *
+ * This program is free software: you can redistribute it and/or
+ * modify it under the terms of the GNU Lesser General Public License
+ * version 3, as published by the Free Software Foundation.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Lesser General Public License for more details.
+ *
+ * You should have received a copy of the GNU Lesser General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Transports that send the certification status requests to the server,
+//! and their errors.
+//!
+//! The requests are sent over HTTP by default. Other transports, such as a
+//! mock, an offline database or a custom RPC, can be passed to
+//! `check_certification_status` instead.
+
+use chrono::{DateTime, Utc};
+use flate2::{write::GzEncoder, Compression};
+use serde_json::Value;
+use std::io::Write;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::cache::{HWCache, StaleStatus};
+use crate::helpers;
+use crate::models::request_validators::CertificationStatusRequest;
+use crate::models::response_validators::CertificationStatusResponse;
+use crate::tls::{self, TlsFailure};
+
+/// Longest server error body kept in the stale reason, in characters.
+const MAX_DETAIL_LENGTH: usize = 1000;
+
+/// Why a request to the server failed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransportError {
+    /// The name of the server couldn't be resolved.
+    Dns(String),
+    /// The TLS handshake failed, or the server certificate was refused.
+    Tls(String),
+    /// The server couldn't be reached for another reason, e.g. the connection
+    /// was refused or the proxy failed.
+    Connection(String),
+    /// The server didn't answer within the configured timeouts.
+    Timeout(String),
+    /// The server refused the request with a 4xx status, other than 429.
+    /// `detail` holds the validation errors it returned, if any.
+    Rejected { status: u16, detail: String },
+    /// The server failed with a 5xx status.
+    ServerFailure { status: u16, detail: String },
+    /// The server answered 429 Too Many Requests, asking to wait
+    /// `retry_after` seconds if it sent a `Retry-After` header.
+    RateLimited { retry_after: Option<u64> },
+    /// The server answered something that isn't a valid response.
+    InvalidResponse(String),
+}
+
+impl std::fmt::Display for TransportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransportError::Dns(message) => {
+                write!(f, "Cannot resolve the server name: {}", message)
+            }
+            TransportError::Tls(message) => write!(f, "TLS error: {}", message),
+            TransportError::Connection(message) => write!(f, "Connecting error: {}", message),
+            TransportError::Timeout(message) => write!(f, "Timeout error: {}", message),
+            TransportError::Rejected { status, detail } => {
+                write!(
+                    f,
+                    "The server rejected the request (HTTP {}): {}",
+                    status, detail
+                )
+            }
+            TransportError::ServerFailure { status, detail } => {
+                write!(f, "Server error (HTTP {}): {}", status, detail)
+            }
+            TransportError::RateLimited {
+                retry_after: Some(seconds),
+            } => write!(f, "Too many requests, retry after {} seconds", seconds),
+            TransportError::RateLimited { retry_after: None } => write!(f, "Too many requests"),
+            TransportError::InvalidResponse(message) => {
+                write!(f, "Invalid response from the server: {}", message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TransportError {}
+
+impl TransportError {
+    /// Returns the stale status the cached answer gets after this error.
+    pub fn stale_status(&self) -> StaleStatus {
+        match self {
+            TransportError::Dns(_) => StaleStatus::DnsError,
+            TransportError::Tls(_) => StaleStatus::TlsError,
+            TransportError::Connection(_) => StaleStatus::ConnectingError,
+            TransportError::Timeout(_) => StaleStatus::Timeout,
+            TransportError::Rejected { status, .. } => {
+                StaleStatus::RequestRejected { status: *status }
+            }
+            TransportError::ServerFailure { status, .. } => {
+                StaleStatus::ServerFailure { status: *status }
+            }
+            TransportError::RateLimited { retry_after } => StaleStatus::RateLimited {
+                retry_after: *retry_after,
+            },
+            TransportError::InvalidResponse(_) => StaleStatus::ServerError,
+        }
+    }
+}
+
+/// What the server answered to a certification status request.
+#[derive(Debug, Clone)]
+pub struct TransportResponse {
+    /// The answer, or None if the server said that the cached answer with the
+    /// ETag of the connection settings is still valid (`304 Not Modified`).
+    pub response: Option<CertificationStatusResponse>,
+    /// Seconds the answer may be cached, if the server specified it, e.g.
+    /// through the `Cache-Control` header. It takes precedence over the
+    /// cache expiration settings.
+    pub max_age: Option<u64>,
+    /// The version of the answer, to revalidate it in the next check.
+    pub etag: Option<String>,
+}
+
+/// How to reach a server, according to the settings.
+#[derive(Clone, Debug)]
+pub struct ConnectionSettings {
+    proxy: Option<String>,
+    connect_timeout: Duration,
+    request_timeout: Duration,
+    tls: tls::TlsSettings,
+    compress_requests: bool,
+    etag: Option<String>,
+}
+
+impl ConnectionSettings {
+    pub(crate) fn new(server_url: &str, cache: &HWCache) -> Self {
+        let (connect_timeout, request_timeout) = cache.get_timeouts();
+        ConnectionSettings {
+            proxy: cache.get_proxy(server_url),
+            connect_timeout: Duration::from_secs(connect_timeout),
+            request_timeout: Duration::from_secs(request_timeout),
+            tls: cache.get_tls_settings(server_url),
+            compress_requests: cache.get_compress_requests(),
+            etag: None,
+        }
+    }
+
+    /// Asks the server to answer `304 Not Modified` if its answer still has
+    /// the given ETag.
+    pub(crate) fn revalidating(mut self, etag: Option<String>) -> Self {
+        self.etag = etag;
+        self
+    }
+
+    /// Returns the proxy to reach the server through, if any.
+    pub fn proxy(&self) -> Option<&str> {
+        self.proxy.as_deref()
+    }
+
+    /// Returns how long to wait for the connection to the server.
+    pub fn connect_timeout(&self) -> Duration {
+        self.connect_timeout
+    }
+
+    /// Returns how long to wait for the whole request.
+    pub fn request_timeout(&self) -> Duration {
+        self.request_timeout
+    }
+
+    /// Returns true if the request body must be compressed with gzip.
+    pub fn compress_requests(&self) -> bool {
+        self.compress_requests
+    }
+
+    /// Returns the ETag of the cached answer, to send in `If-None-Match`.
+    pub fn etag(&self) -> Option<&str> {
+        self.etag.as_deref()
+    }
+}
+
+/// Sends certification status requests to a server, or answers them by
+/// other means.
+///
+/// Requests are sent from another thread, so that checks can be cancelled:
+/// the request of a cancelled check is abandoned rather than interrupted, so
+/// it should end by itself within the timeouts of the connection settings.
+pub trait Transport: Send + Sync {
+    /// Returns the answer to `request` of the certification status endpoint
+    /// at `url`.
+    fn send(
+        &self,
+        url: &str,
+        request: &CertificationStatusRequest,
+        connection: &ConnectionSettings,
+    ) -> Result<TransportResponse, TransportError>;
+}
+
+/// What the library is used by, reported to the server in the User-Agent so
+/// that the clients can be told apart.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Integration {
+    /// An application embedding the library.
+    #[default]
+    Library,
+    /// The `hwctl` CLI tool.
+    Cli,
+    /// The `hwctl-daemon` service.
+    Daemon,
+    /// The Python bindings.
+    Python,
+}
+
+impl Integration {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Integration::Library => "library",
+            Integration::Cli => "cli",
+            Integration::Daemon => "daemon",
+            Integration::Python => "python",
+        }
+    }
+}
+
+/// Returns the User-Agent identifying the client to the server, e.g.
+/// `hwlib/0.11.1 (daemon; snap 42; x86_64)`. The snap revision is only
+/// included when running in a snap.
+pub fn user_agent(integration: Integration) -> String {
+    format_user_agent(
+        integration,
+        std::env::var("SNAP_REVISION").ok().as_deref(),
+        std::env::consts::ARCH,
+    )
+}
+
+fn format_user_agent(integration: Integration, snap_revision: Option<&str>, arch: &str) -> String {
+    let snap = snap_revision
+        .filter(|revision| !revision.is_empty())
+        .map(|revision| format!("snap {}; ", revision))
+        .unwrap_or_default();
+    format!(
+        "hwlib/{} ({}; {}{})",
+        env!("CARGO_PKG_VERSION"),
+        integration.name(),
+        snap,
+        arch
+    )
+}
+
+/// Sends the requests over HTTP, with the proxy, timeouts and TLS settings of
+/// the connection. The default transport.
+#[derive(Debug, Clone)]
+pub struct HttpTransport {
+    user_agent: String,
+}
+
+impl HttpTransport {
+    /// Identifies the requests as sent by `integration`.
+    pub fn new(integration: Integration) -> Self {
+        HttpTransport {
+            user_agent: user_agent(integration),
+        }
+    }
+
+    /// Identifies the requests with `user_agent` instead, e.g. for
+    /// applications embedding the library that want to be told apart.
+    pub fn with_user_agent(user_agent: impl Into<String>) -> Self {
+        HttpTransport {
+            user_agent: user_agent.into(),
+        }
+    }
+
+    /// Returns the User-Agent sent with the requests.
+    pub fn user_agent(&self) -> &str {
+        &self.user_agent
+    }
+}
+
+impl Default for HttpTransport {
+    fn default() -> Self {
+        HttpTransport::new(Integration::Library)
+    }
+}
+
+impl Transport for HttpTransport {
+    fn send(
+        &self,
+        url: &str,
+        request: &CertificationStatusRequest,
+        connection: &ConnectionSettings,
+    ) -> Result<TransportResponse, TransportError> {
+        let connector = tls::TlsConnector::new(&connection.tls)
+            .map_err(|error| TransportError::Tls(format!("{:#}", error)))?;
+        let mut agent = ureq::AgentBuilder::new()
+            .user_agent(&self.user_agent)
+            .tls_connector(Arc::new(connector))
+            .timeout_connect(connection.connect_timeout)
+            .timeout(connection.request_timeout);
+        if let Some(proxy) = &connection.proxy {
+            let proxy = ureq::Proxy::new(proxy)
+                .map_err(|error| TransportError::Connection(format!("invalid proxy: {}", error)))?;
+            agent = agent.proxy(proxy);
+        }
+        let mut post = agent
+            .build()
+            .post(url)
+            .set("Content-Type", "application/json");
+        if let Some(etag) = &connection.etag {
+            post = post.set("If-None-Match", etag);
+        }
+        let body = serde_json::to_vec(request)
+            .map_err(|error| TransportError::Connection(error.to_string()))?;
+        let response = match connection.compress_requests {
+            true => post
+                .set("Content-Encoding", "gzip")
+                .send_bytes(&gzip(&body)?),
+            false => post.send_bytes(&body),
+        }
+        .map_err(from_ureq_error)?;
+        let max_age = response
+            .header("cache-control")
+            .and_then(helpers::parse_max_age);
+        let etag = response.header("etag").map(str::to_string);
+        if response.status() == 304 {
+            if connection.etag.is_none() {
+                return Err(TransportError::InvalidResponse(
+                    "Not Modified answer to a request without ETag".to_string(),
+                ));
+            }
+            return Ok(TransportResponse {
+                response: None,
+                max_age,
+                etag: etag.or_else(|| connection.etag.clone()),
+            });
+        }
+        let response = response
+            .into_json::<CertificationStatusResponse>()
+            .map_err(|error| match is_timeout(&error) {
+                true => TransportError::Timeout(error.to_string()),
+                false => TransportError::InvalidResponse(error.to_string()),
+            })?;
+        Ok(TransportResponse {
+            response: Some(response),
+            max_age,
+            etag,
+        })
+    }
+}
+
+/// Compresses a request body with gzip.
+fn gzip(body: &[u8]) -> Result<Vec<u8>, TransportError> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(body)
+        .and_then(|_| encoder.finish())
+        .map_err(|error| {
+            TransportError::Connection(format!("cannot compress the request: {}", error))
+        })
+}
+
+/// Returns true if `error` was caused by a timeout.
+fn is_timeout(error: &(dyn std::error::Error + 'static)) -> bool {
+    let mut source = Some(error);
+    while let Some(error) = source {
+        let io_error = error.downcast_ref::<std::io::Error>();
+        if io_error.is_some_and(|error| error.kind() == std::io::ErrorKind::TimedOut) {
+            return true;
+        }
+        source = error.source();
+    }
+    false
+}
+
+/// Returns true if `error` was caused by the TLS handshake.
+fn is_tls_failure(error: &(dyn std::error::Error + 'static)) -> bool {
+    let mut source = Some(error);
+    while let Some(error) = source {
+        // I/O errors don't report the error they wrap as their source.
+        let inner = error
+            .downcast_ref::<std::io::Error>()
+            .and_then(|error| error.get_ref());
+        if error.is::<TlsFailure>() || inner.is_some_and(|inner| inner.is::<TlsFailure>()) {
+            return true;
+        }
+        source = error.source();
+    }
+    false
+}
+
+/// Returns a validation error of the server as `<location>: <message>`.
+fn validation_error(error: &Value) -> String {
+    let location = error["loc"].as_array().map(|location| {
+        location
+            .iter()
+            .map(|part| part.as_str().map_or(part.to_string(), str::to_string))
+            .collect::<Vec<_>>()
+            .join(".")
+    });
+    match (location, error["msg"].as_str()) {
+        (Some(location), Some(message)) => format!("{}: {}", location, message),
+        (None, Some(message)) => message.to_string(),
+        _ => error.to_string(),
+    }
+}
+
+/// Returns the error details in the body of an error response: the `detail`
+/// field of the JSON errors of the server, or the body itself.
+fn error_detail(body: &str) -> String {
+    let detail = serde_json::from_str::<Value>(body)
+        .ok()
+        .and_then(|body| match body.get("detail")? {
+            Value::String(detail) => Some(detail.clone()),
+            Value::Array(errors) => Some(
+                errors
+                    .iter()
+                    .map(validation_error)
+                    .collect::<Vec<_>>()
+                    .join("; "),
+            ),
+            detail => Some(detail.to_string()),
+        })
+        .unwrap_or_else(|| body.trim().to_string());
+    match detail.char_indices().nth(MAX_DETAIL_LENGTH) {
+        Some((index, _)) => format!("{}…", &detail[..index]),
+        None => detail,
+    }
+}
+
+/// Returns the seconds to wait according to a `Retry-After` header, which
+/// holds either a number of seconds or an HTTP date.
+fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<u64> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(seconds);
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    Some((date.with_timezone(&Utc) - now).num_seconds().max(0) as u64)
+}
+
+/// Returns the error of an HTTP request.
+pub(crate) fn from_ureq_error(error: ureq::Error) -> TransportError {
+    match error {
+        ureq::Error::Status(429, response) => TransportError::RateLimited {
+            retry_after: response
+                .header("retry-after")
+                .and_then(|value| parse_retry_after(value, Utc::now())),
+        },
+        ureq::Error::Status(status, response) => {
+            let detail = error_detail(&response.into_string().unwrap_or_default());
+            match status {
+                400..=499 => TransportError::Rejected { status, detail },
+                _ => TransportError::ServerFailure { status, detail },
+            }
+        }
+        ureq::Error::Transport(transport) => {
+            let message = transport.to_string();
+            if is_timeout(&transport) {
+                TransportError::Timeout(message)
+            } else if is_tls_failure(&transport) {
+                TransportError::Tls(message)
+            } else {
+                match transport.kind() {
+                    ureq::ErrorKind::Dns => TransportError::Dns(message),
+                    ureq::ErrorKind::BadStatus | ureq::ErrorKind::BadHeader => {
+                        TransportError::InvalidResponse(message)
+                    }
+                    _ => TransportError::Connection(message),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status_error(response: &str) -> ureq::Error {
+        let response: ureq::Response = response.parse().unwrap();
+        ureq::Error::Status(response.status(), response)
+    }
+
+    #[test]
+    fn test_status_errors() {
+        let error = from_ureq_error(status_error(
+            "HTTP/1.1 422 Unprocessable Entity\r\nContent-Type: application/json\r\n\r\n\
+             {\"detail\": [{\"loc\": [\"body\", \"os\", \"version\"], \"msg\": \"Field required\", \"type\": \"missing\"}]}",
+        ));
+        assert_eq!(
+            error,
+            TransportError::Rejected {
+                status: 422,
+                detail: "body.os.version: Field required".to_string()
+            }
+        );
+        assert_eq!(
+            error.stale_status(),
+            StaleStatus::RequestRejected { status: 422 }
+        );
+
+        let error = from_ureq_error(status_error(
+            "HTTP/1.1 503 Service Unavailable\r\n\r\nmaintenance",
+        ));
+        assert_eq!(
+            error,
+            TransportError::ServerFailure {
+                status: 503,
+                detail: "maintenance".to_string()
+            }
+        );
+        assert_eq!(error.to_string(), "Server error (HTTP 503): maintenance");
+
+        let error = from_ureq_error(status_error(
+            "HTTP/1.1 429 Too Many Requests\r\nRetry-After: 120\r\n\r\n",
+        ));
+        assert_eq!(
+            error.stale_status(),
+            StaleStatus::RateLimited {
+                retry_after: Some(120)
+            }
+        );
+    }
+
+    #[test]
+    fn test_error_detail() {
+        assert_eq!(error_detail(r#"{"detail": "Not Found"}"#), "Not Found");
+        assert_eq!(error_detail("  Bad Gateway\n"), "Bad Gateway");
+        assert_eq!(
+            error_detail(r#"{"detail": [{"loc": ["body", "processor", 0], "msg": "invalid"}]}"#),
+            "body.processor.0: invalid"
+        );
+        assert_eq!(
+            error_detail(&"x".repeat(MAX_DETAIL_LENGTH + 1))
+                .chars()
+                .count(),
+            MAX_DETAIL_LENGTH + 1
+        );
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        let now = DateTime::parse_from_rfc3339("2015-10-21T07:28:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(parse_retry_after("120", now), Some(120));
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:30:00 GMT", now),
+            Some(120)
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:00:00 GMT", now),
+            Some(0)
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+
+    #[test]
+    fn test_user_agent() {
+        let version = env!("CARGO_PKG_VERSION");
+        assert_eq!(
+            format_user_agent(Integration::Daemon, Some("42"), "x86_64"),
+            format!("hwlib/{} (daemon; snap 42; x86_64)", version)
+        );
+        assert_eq!(
+            format_user_agent(Integration::Python, None, "aarch64"),
+            format!("hwlib/{} (python; aarch64)", version)
+        );
+        assert_eq!(
+            HttpTransport::with_user_agent("my-app/1.0").user_agent(),
+            "my-app/1.0"
+        );
+    }
+
+    /// A request received by `serve_once`.
+    struct ReceivedRequest {
+        /// The header lines, in lowercase.
+        headers: Vec<String>,
+        body: Vec<u8>,
+    }
+
+    /// Answers a single HTTP request with `response`, returning the URL to
+    /// send it to and what was received.
+    fn serve_once(response: &'static str) -> (String, std::thread::JoinHandle<ReceivedRequest>) {
+        use std::io::{BufRead, BufReader, Read};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut headers = Vec::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                headers.push(line.trim().to_lowercase());
+            }
+            let length = headers
+                .iter()
+                .find_map(|header| header.strip_prefix("content-length: "))
+                .map_or(0, |length| length.parse().unwrap());
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            reader.get_mut().write_all(response.as_bytes()).unwrap();
+            ReceivedRequest { headers, body }
+        });
+        (url, server)
+    }
+
+    fn test_connection(compress_requests: bool, etag: Option<&str>) -> ConnectionSettings {
+        ConnectionSettings {
+            proxy: None,
+            connect_timeout: Duration::from_secs(5),
+            request_timeout: Duration::from_secs(5),
+            tls: tls::TlsSettings::default(),
+            compress_requests,
+            etag: etag.map(str::to_string),
+        }
+    }
+
+    fn test_request() -> CertificationStatusRequest {
+        use crate::helpers::test_utils;
+
+        let content = std::fs::read_to_string(test_utils::get_test_filepath(
+            "amd64/dell_xps13/request.json",
+        ))
+        .unwrap();
+        let content = test_utils::apply_vars(
+            content,
+            &[
+                ("CODENAME", "noble"),
+                ("KERNEL_VERSION", "6.8.0-31-generic"),
+                ("KERNEL_MODULES", "[]"),
+                ("RELEASE", "24.04"),
+            ],
+        );
+        serde_json::from_str(&content).unwrap()
+    }
+
+    const NOT_SEEN: &str = "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                            ETag: \"v1\"\r\nContent-Length: 22\r\n\r\n{\"status\": \"Not Seen\"}";
+
+    #[test]
+    fn test_requests_carry_the_user_agent() {
+        let (url, server) = serve_once(NOT_SEEN);
+        let transport = HttpTransport::new(Integration::Cli);
+        let answer = transport
+            .send(&url, &test_request(), &test_connection(false, None))
+            .unwrap();
+        assert!(matches!(
+            answer.response,
+            Some(CertificationStatusResponse::NotSeen)
+        ));
+        assert_eq!(answer.etag.as_deref(), Some("\"v1\""));
+        let expected = format!("user-agent: {}", user_agent(Integration::Cli)).to_lowercase();
+        let received = server.join().unwrap();
+        assert!(received.headers.contains(&expected));
+        assert!(!received
+            .headers
+            .iter()
+            .any(|header| header.starts_with("if-none-match")));
+    }
+
+    #[test]
+    fn test_compressed_requests() {
+        use flate2::read::GzDecoder;
+        use std::io::Read;
+
+        let (url, server) = serve_once(NOT_SEEN);
+        let request = test_request();
+        HttpTransport::default()
+            .send(&url, &request, &test_connection(true, None))
+            .unwrap();
+        let received = server.join().unwrap();
+        assert!(received
+            .headers
+            .contains(&"content-encoding: gzip".to_string()));
+        let mut body = String::new();
+        GzDecoder::new(received.body.as_slice())
+            .read_to_string(&mut body)
+            .unwrap();
+        assert_eq!(
+            serde_json::from_str::<Value>(&body).unwrap(),
+            serde_json::to_value(&request).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_not_modified() {
+        let (url, server) =
+            serve_once("HTTP/1.1 304 Not Modified\r\nCache-Control: max-age=600\r\n\r\n");
+        let answer = HttpTransport::default()
+            .send(
+                &url,
+                &test_request(),
+                &test_connection(false, Some("\"v1\"")),
+            )
+            .unwrap();
+        assert!(answer.response.is_none());
+        assert_eq!(answer.max_age, Some(600));
+        assert_eq!(answer.etag.as_deref(), Some("\"v1\""));
+        let received = server.join().unwrap();
+        assert!(received
+            .headers
+            .contains(&"if-none-match: \"v1\"".to_string()));
+
+        // Nothing can be revalidated without an ETag.
+        let (url, _) = serve_once("HTTP/1.1 304 Not Modified\r\n\r\n");
+        let error = HttpTransport::default()
+            .send(&url, &test_request(), &test_connection(false, None))
+            .unwrap_err();
+        assert_eq!(error.stale_status(), StaleStatus::ServerError);
+    }
+
+    #[test]
+    fn test_connection_errors() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+        let error = ureq::get(&format!("http://127.0.0.1:{}/", port))
+            .call()
+            .unwrap_err();
+        assert_eq!(
+            from_ureq_error(error).stale_status(),
+            StaleStatus::ConnectingError
+        );
+
+        let error = ureq::get("http://hwctl.invalid/").call().unwrap_err();
+        assert_eq!(from_ureq_error(error).stale_status(), StaleStatus::DnsError);
+    }
+}
@@ -77,7 +77,7 @@ fn test_certification_request(dir_path: &str) -> Result<()> {
     let api_url = std::env::var("API_URL").expect("API_URL environment variable must be specified");
 
     let mut local_cache = HWCache::new(None);
-    local_cache.set_allow_custom_url_enabled(true);
+    local_cache.set_allow_custom_url_enabled(true)?;
 
     let cert_request = CertificationStatusRequest::new(get_test_device_paths(dir_path))?;
     let response = check_certification_status(api_url, hwlib::CheckCertificationSource::Server, &cert_request, Some(&mut local_cache));
@@ -95,7 +95,7 @@ fn test_certification_request(dir_path: &str) -> Result<()> {
 #[test]
 fn test_server_connection_error() -> Result<()> {
     let mut local_cache = HWCache::new(None);
-    local_cache.set_allow_custom_url_enabled(true);
+    local_cache.set_allow_custom_url_enabled(true)?;
 
     let result = check_certification_status(
         "http://non-existent-server:8080".to_string(),